@@ -1,6 +1,53 @@
-use crate::memory::{Memory, ReadOnlyMemory};
-use crate::{clint::Clint, plic::Plic, uart::Uart, vio:::Vio};
+use crate::csr::{CsrFile, IRQ_MEI, IRQ_SEI};
+use crate::memory::{Memory, MemError, ReadOnlyMemory, BASE, BYTE, DOUBLEWORD, HALFWORD, WORD};
+use crate::{clint::Clint, plic::{Plic, CLAIM_OFFSET}, uart::Uart, vio::Vio};
 
+// The physical address map, the same as the QEMU virt board. Everything
+// from DRAM_BASE up is DRAM, the gaps between devices are unmapped.
+pub const ROM_BASE: u64 = 0x1000;
+pub const ROM_SIZE: u64 = 0xf000;
+pub const CLINT_BASE: u64 = 0x200_0000;
+pub const CLINT_SIZE: u64 = 0x1_0000;
+pub const PLIC_BASE: u64 = 0xc00_0000;
+pub const PLIC_SIZE: u64 = 0x400_0000;
+pub const UART_BASE: u64 = 0x1000_0000;
+pub const UART_SIZE: u64 = 0x100;
+pub const VIRTIO_BASE: u64 = 0x1000_1000;
+pub const VIRTIO_SIZE: u64 = 0x1000;
+pub const DRAM_BASE: u64 = BASE;
+
+// Where an access lands.
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum Region {
+    Rom,
+    Clint,
+    Plic,
+    Uart,
+    Virtio,
+    Dram,
+}
+
+// The region holding all of [addr, addr + size), None when it is unmapped
+// or straddles two regions.
+fn region(addr: u64, size: u64) -> Option<Region> {
+    let end = addr.checked_add(size)?;
+    let regions = [
+        (ROM_BASE, ROM_SIZE, Region::Rom),
+        (CLINT_BASE, CLINT_SIZE, Region::Clint),
+        (PLIC_BASE, PLIC_SIZE, Region::Plic),
+        (UART_BASE, UART_SIZE, Region::Uart),
+        (VIRTIO_BASE, VIRTIO_SIZE, Region::Virtio),
+        (DRAM_BASE, u64::MAX - DRAM_BASE, Region::Dram),
+    ];
+    regions
+        .iter()
+        .find(|(base, len, _)| addr >= *base && end <= base + len)
+        .map(|(_, _, region)| *region)
+}
+
+/// The system bus of a hart. It decodes physical addresses and routes each
+/// access to the ROM, a device or DRAM, which has to be mapped at
+/// `DRAM_BASE`. Accesses to unmapped addresses fail.
 #[derive(Clone, Debug)]
 pub struct Bus<C, P, U, I, M, R>
 where
@@ -19,17 +66,338 @@ where
     rom: R,
 }
 
-
-impl<C, P, U, I, M, R> Bus<C, P, U, I, M, R> {
-    pub fn new() -> Self {
+impl<C, P, U, I, M, R> Bus<C, P, U, I, M, R>
+where
+    C: Clone + Clint,
+    P: Clone + Plic,
+    U: Clone + Uart,
+    I: Clone + Vio,
+    M: Clone + Memory<RegValue = u64, Bytes = Vec<u8>, Error = MemError>,
+    R: Clone + ReadOnlyMemory
+{
+    pub fn new(dram: M, rom: R) -> Self {
         Bus {
             clint: C::default(),
             plic: P::default(),
             uart: U::default(),
             io: I::default(),
-            dram: M::default(),
-            rom: R::default()
+            dram,
+            rom,
+        }
+    }
+
+    pub fn rom(&self) -> &R {
+        &self.rom
+    }
+
+    // Reads a device or memory register without side effects. The UART is
+    // only read through `load`.
+    fn read_region(&self, addr: u64, size: u8) -> Result<u64, MemError> {
+        match region(addr, u64::from(size / 8)) {
+            Some(Region::Rom) => self.rom.read(addr - ROM_BASE, size),
+            Some(Region::Clint) => self.clint.read(addr, size).map_err(|_| MemError::LoadAccessFault),
+            Some(Region::Plic) => self.plic.read(addr, size).map_err(|_| MemError::LoadAccessFault),
+            Some(Region::Virtio) => self.io.read(addr, size).map_err(|_| MemError::LoadAccessFault),
+            Some(Region::Dram) => self.dram.read(&addr, size),
+            Some(Region::Uart) => Err(MemError::LoadAccessFault),
+            None => Err(MemError::OutOfBounds),
+        }
+    }
+
+    fn dram_region(addr: u64, size: u64) -> Result<(), MemError> {
+        match region(addr, size.max(1)) {
+            Some(Region::Dram) => Ok(()),
+            _ => Err(MemError::OutOfBounds),
+        }
+    }
+}
+
+impl<C, P, U, I, M, R> Default for Bus<C, P, U, I, M, R>
+where
+    C: Clone + Clint,
+    P: Clone + Plic,
+    U: Clone + Uart,
+    I: Clone + Vio,
+    M: Clone + Memory<RegValue = u64, Bytes = Vec<u8>, Error = MemError>,
+    R: Clone + ReadOnlyMemory
+{
+    fn default() -> Self {
+        Bus::new(M::default(), R::default())
+    }
+}
+
+impl<C, P, U, I, M, R> Memory for Bus<C, P, U, I, M, R>
+where
+    C: Clone + Clint<State = CsrFile>,
+    P: Clone + Plic,
+    U: Clone + Uart,
+    I: Clone + Vio,
+    M: Clone + Memory<RegValue = u64, Bytes = Vec<u8>, Error = MemError>,
+    R: Clone + ReadOnlyMemory
+{
+    type RegValue = u64;
+    type Bytes = Vec<u8>;
+    type Error = MemError;
+
+    // Only DRAM can be initialized, page flags and indices are those of DRAM.
+    fn init(&mut self, addr: u64, size: u64, flags: u8, source: Option<Self::Bytes>, offset: u64) -> Result<(), Self::Error> {
+        Self::dram_region(addr, size)?;
+        self.dram.init(addr, size, flags, source, offset)
+    }
+
+    fn get_flag(&mut self, index: u64) -> Result<u8, Self::Error> {
+        self.dram.get_flag(index)
+    }
+
+    fn set_flag(&mut self, index: u64, flag: u8) -> Result<(), Self::Error> {
+        self.dram.set_flag(index, flag)
+    }
+
+    fn clear_flag(&mut self, index: u64, flag: u8) -> Result<(), Self::Error> {
+        self.dram.clear_flag(index, flag)
+    }
+
+    fn get_indices(&self, addr: u64, size: u64) -> Result<(u64, u64), Self::Error> {
+        Self::dram_region(addr, size)?;
+        self.dram.get_indices(addr, size)
+    }
+
+    // Device registers can be written, the ROM can't.
+    fn check_write(&self, addr: u64, size: u64) -> Result<(), Self::Error> {
+        match region(addr, size.max(1)) {
+            Some(Region::Dram) => self.dram.check_write(addr, size),
+            Some(Region::Rom) => Err(MemError::NotWritable),
+            Some(_) => Ok(()),
+            None => Err(MemError::OutOfBounds),
+        }
+    }
+
+    // Code runs from the ROM or DRAM, never from a device.
+    fn execute_readhw(&mut self, addr: u64) -> Result<Self::RegValue, Self::Error> {
+        match region(addr, 2) {
+            Some(Region::Dram) => self.dram.execute_readhw(addr),
+            Some(Region::Rom) => self.rom.read(addr - ROM_BASE, HALFWORD),
+            Some(_) => Err(MemError::NotExecutable),
+            None => Err(MemError::OutOfBounds),
+        }
+    }
+
+    fn execute_readw(&mut self, addr: u64) -> Result<Self::RegValue, Self::Error> {
+        match region(addr, 4) {
+            Some(Region::Dram) => self.dram.execute_readw(addr),
+            Some(Region::Rom) => self.rom.read(addr - ROM_BASE, WORD),
+            Some(_) => Err(MemError::NotExecutable),
+            None => Err(MemError::OutOfBounds),
+        }
+    }
+
+    fn read(&self, addr: &Self::RegValue, size: u8) -> Result<Self::RegValue, Self::Error> {
+        self.read_region(*addr, size)
+    }
+
+    fn readb(&self, addr: &Self::RegValue) -> Result<Self::RegValue, Self::Error> {
+        self.read_region(*addr, BYTE)
+    }
+
+    fn readhw(&self, addr: &Self::RegValue) -> Result<Self::RegValue, Self::Error> {
+        self.read_region(*addr, HALFWORD)
+    }
+
+    fn readw(&self, addr: &Self::RegValue) -> Result<Self::RegValue, Self::Error> {
+        self.read_region(*addr, WORD)
+    }
+
+    fn readdw(&self, addr: &Self::RegValue) -> Result<Self::RegValue, Self::Error> {
+        self.read_region(*addr, DOUBLEWORD)
+    }
+
+    fn load(&mut self, addr: &Self::RegValue, size: u8) -> Result<Self::RegValue, Self::Error> {
+        match region(*addr, u64::from(size / 8)) {
+            // Reading a claim register claims the interrupt.
+            Some(Region::Plic) => {
+                let irq = self.read_region(*addr, size)?;
+                if *addr >= P::TRESH_CLAIM_START && (*addr - P::TRESH_CLAIM_START) % P::CTX_OFFSET == CLAIM_OFFSET {
+                    self.plic.update_claim((*addr - P::TRESH_CLAIM_START) / P::CTX_OFFSET, irq);
+                }
+                Ok(irq)
+            },
+            Some(Region::Uart) => self.uart.read(*addr - UART_BASE, size).map_err(|_| MemError::LoadAccessFault),
+            _ => self.read_region(*addr, size),
         }
     }
-} 
 
+    fn keeps_time(&self) -> bool {
+        self.clint.keeps_time()
+    }
+
+    // Devices raise their sources at the PLIC, whose machine and supervisor
+    // contexts of the hart drive MEIP and SEIP.
+    fn increment(&mut self, csr: &mut CsrFile) {
+        self.clint.increment(csr);
+        self.uart.receive();
+        if self.uart.is_interrupting() {
+            self.plic.update_pending(U::UART_IRQ);
+        }
+        if self.io.is_interrupting() {
+            self.plic.update_pending(I::VIRTIO_IRQ);
+        }
+        let ctx = 2 * csr.hartid;
+        csr.mip = (csr.mip & !(IRQ_MEI | IRQ_SEI))
+            | if self.plic.is_interrupting(ctx) { IRQ_MEI } else { 0 }
+            | if self.plic.is_interrupting(ctx + 1) { IRQ_SEI } else { 0 };
+    }
+
+    // Bulk copies only reach DRAM.
+    fn read_array(&self, addr: Self::RegValue, size: u64) -> Result<Self::Bytes, Self::Error> {
+        Self::dram_region(addr, size)?;
+        self.dram.read_array(addr, size)
+    }
+
+    fn write_array(&mut self, addr: Self::RegValue, val: Self::Bytes) -> Result<(), Self::Error> {
+        Self::dram_region(addr, val.len() as u64)?;
+        self.dram.write_array(addr, val)
+    }
+
+    fn write(&mut self, addr: u64, value: u64, size: u8) -> Result<(), Self::Error> {
+        match region(addr, u64::from(size / 8)) {
+            Some(Region::Dram) => self.dram.write(addr, value, size),
+            Some(Region::Clint) => self.clint.write(addr, value, size).map_err(|_| MemError::StoreAMOAccessFault),
+            Some(Region::Plic) => self.plic.write(addr, value, size).map_err(|_| MemError::StoreAMOAccessFault),
+            Some(Region::Uart) => self.uart.write(addr - UART_BASE, value as u8, size).map_err(|_| MemError::StoreAMOAccessFault),
+            Some(Region::Virtio) => self.io.write(addr, value, size).map_err(|_| MemError::StoreAMOAccessFault),
+            Some(Region::Rom) => Err(MemError::NotWritable),
+            None => Err(MemError::OutOfBounds),
+        }
+    }
+
+    fn writeb(&mut self, addr: Self::RegValue, val: Self::RegValue) -> Result<(), Self::Error> {
+        self.write(addr, val, BYTE)
+    }
+
+    fn writehw(&mut self, addr: Self::RegValue, val: Self::RegValue) -> Result<(), Self::Error> {
+        self.write(addr, val, HALFWORD)
+    }
+
+    fn writew(&mut self, addr: Self::RegValue, val: Self::RegValue) -> Result<(), Self::Error> {
+        self.write(addr, val, WORD)
+    }
+
+    fn writedw(&mut self, addr: u64, val: u64) -> Result<(), Self::Error> {
+        self.write(addr, val, DOUBLEWORD)
+    }
+
+    fn into_u64(&self, val: &Self::RegValue) -> u64 {
+        *val
+    }
+
+    fn into_i64(&self, val: &Self::RegValue) -> i64 {
+        *val as i64
+    }
+
+    fn into_u32(&self, val: &Self::RegValue) -> u32 {
+        *val as u32
+    }
+
+    fn into_i32(&self, val: &Self::RegValue) -> i32 {
+        *val as i32
+    }
+}
+
+/// A device slot with nothing behind it: every access to its registers
+/// fails. It stands in for the devices a bus is built without.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Unmapped;
+
+impl Clint for Unmapped {
+    type Msip = ();
+    type Mtimecmp = ();
+    type Mtime = ();
+    type State = CsrFile;
+    type Exception = MemError;
+
+    fn increment(&mut self, _state: &mut Self::State) {}
+
+    fn keeps_time(&self) -> bool {
+        false
+    }
+
+    fn read(&self, _addr: u64, _size: u8) -> Result<u64, Self::Exception> {
+        Err(MemError::LoadAccessFault)
+    }
+
+    fn write(&mut self, _addr: u64, _value: u64, _size: u8) -> Result<(), Self::Exception> {
+        Err(MemError::StoreAMOAccessFault)
+    }
+}
+
+impl Plic for Unmapped {
+    type Exception = MemError;
+
+    fn update_pending(&mut self, _irq: u64) {}
+
+    fn clear_pending(&mut self, _irq: u64) {}
+
+    fn update_claim(&mut self, _ctx: u64, _irq: u64) {}
+
+    fn is_enable(&self, _ctx: u64, _irq: u64) -> bool {
+        false
+    }
+
+    fn is_interrupting(&self, _ctx: u64) -> bool {
+        false
+    }
+
+    fn read(&self, _addr: u64, _size: u8) -> Result<u64, Self::Exception> {
+        Err(MemError::LoadAccessFault)
+    }
+
+    fn write(&mut self, _addr: u64, _value: u64, _size: u8) -> Result<(), Self::Exception> {
+        Err(MemError::StoreAMOAccessFault)
+    }
+}
+
+impl Uart for Unmapped {
+    type Interrupting = ();
+    type ReceiverTransmitter = ();
+    type Exception = MemError;
+
+    fn is_interrupting(&self) -> bool {
+        false
+    }
+
+    fn read(&mut self, _index: u64, _size: u8) -> Result<u64, Self::Exception> {
+        Err(MemError::LoadAccessFault)
+    }
+
+    fn write(&mut self, _index: u64, _value: u8, _size: u8) -> Result<(), Self::Exception> {
+        Err(MemError::StoreAMOAccessFault)
+    }
+}
+
+impl Vio for Unmapped {
+    type VirtualQueueAddress = ();
+    type VirtualQueueDescriptor = ();
+    type VirtualQueueAvailability = ();
+    type Exception = MemError;
+    type Cpu = ();
+
+    fn new_virtual_queue_address(self) -> Self::VirtualQueueAddress {}
+
+    fn new_virtual_queue_descriptor(self) -> Self::VirtualQueueDescriptor {}
+
+    fn new_virtual_queue_availability(self, _cpu: Self::Cpu) -> Result<Self::VirtualQueueAvailability, Self::Exception> {
+        Err(MemError::LoadAccessFault)
+    }
+
+    fn is_interrupting(&self) -> bool {
+        false
+    }
+
+    fn read(&self, _addr: u64, _size: u8) -> Result<u64, Self::Exception> {
+        Err(MemError::LoadAccessFault)
+    }
+
+    fn write(&mut self, _addr: u64, _value: u64, _size: u8) -> Result<(), Self::Exception> {
+        Err(MemError::StoreAMOAccessFault)
+    }
+}
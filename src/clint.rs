@@ -1,10 +1,17 @@
+use crate::bus::CLINT_BASE;
+use crate::csr::{CsrFile, IRQ_MSI, IRQ_MTI};
+use crate::memory::{MemError, DOUBLEWORD, WORD};
+
+// The core local interruptor: the software interrupt pending bit and the
+// timer of each hart. Registers are given by absolute address, an access
+// to [START, END) reaches the register.
 pub trait Clint: Default {
-    const MSIP_START: u64;
-    const MSIP_END: u64;
-    const MTIMECMP_START: u64;
-    const MTIMECMP_END: u64;
-    const MTIME_START: u64;
-    const MTIME_END: u64;
+    const MSIP_START: u64 = CLINT_BASE;
+    const MSIP_END: u64 = CLINT_BASE + 0x4;
+    const MTIMECMP_START: u64 = CLINT_BASE + 0x4000;
+    const MTIMECMP_END: u64 = CLINT_BASE + 0x4008;
+    const MTIME_START: u64 = CLINT_BASE + 0xbff8;
+    const MTIME_END: u64 = CLINT_BASE + 0xc000;
     type Msip;
     type Mtimecmp;
     type Mtime;
@@ -12,10 +19,139 @@ pub trait Clint: Default {
     type Exception: std::error::Error;
     
     fn increment(&mut self, state: &mut Self::State);
+    // Whether mtime is the time of the harts, `increment` then copies it to
+    // their time CSR.
+    fn keeps_time(&self) -> bool {
+        true
+    }
     fn read(&self, addr: u64, size: u8) -> Result<u64, Self::Exception>;
-    fn write(&mut self, addr; u64, value: u64, size: u8) -> Result<(), Self::Exception>;
+    fn write(&mut self, addr: u64, value: u64, size: u8) -> Result<(), Self::Exception>;
     fn new() -> Self {
         Self::default()
     }
 }
 
+// Bytes between the registers of two harts.
+const MSIP_STRIDE: u64 = 4;
+const MTIMECMP_STRIDE: u64 = 8;
+
+/// A CLINT for one or more harts: each hart has a software interrupt bit
+/// in msip and a timer compare register, mtime is shared. The timer
+/// interrupt of a hart is pending while mtime >= its mtimecmp.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SoftClint {
+    msip: Vec<u32>,
+    mtimecmp: Vec<u64>,
+    mtime: u64,
+}
+
+impl SoftClint {
+    pub fn with_harts(harts: usize) -> SoftClint {
+        SoftClint {
+            msip: vec![0; harts],
+            // The timer stays quiet until software programs it.
+            mtimecmp: vec![u64::MAX; harts],
+            mtime: 0,
+        }
+    }
+
+    pub fn mtime(&self) -> u64 {
+        self.mtime
+    }
+
+    // The register at `addr` as (value, offset of the access in it), None
+    // for registers of harts that don't exist.
+    fn register(&self, addr: u64) -> Option<(u64, u64)> {
+        match addr {
+            Self::MSIP_START..Self::MSIP_END => {
+                let hart = ((addr - Self::MSIP_START) / MSIP_STRIDE) as usize;
+                self.msip.get(hart).map(|msip| (u64::from(*msip), (addr - Self::MSIP_START) % MSIP_STRIDE))
+            },
+            Self::MTIMECMP_START..Self::MTIMECMP_END => {
+                let hart = ((addr - Self::MTIMECMP_START) / MTIMECMP_STRIDE) as usize;
+                self.mtimecmp.get(hart).map(|cmp| (*cmp, (addr - Self::MTIMECMP_START) % MTIMECMP_STRIDE))
+            },
+            Self::MTIME_START..Self::MTIME_END => Some((self.mtime, addr - Self::MTIME_START)),
+            _ => None,
+        }
+    }
+}
+
+impl Default for SoftClint {
+    fn default() -> SoftClint {
+        SoftClint::with_harts(1)
+    }
+}
+
+// Registers are read and written as whole words or, for the 64 bit ones,
+// as either of their 32 bit halves.
+fn check_access(offset: u64, size: u8, width: u64) -> Result<(), MemError> {
+    match size {
+        WORD if offset.is_multiple_of(4) => Ok(()),
+        DOUBLEWORD if offset == 0 && width == 8 => Ok(()),
+        _ => Err(MemError::OutOfBounds),
+    }
+}
+
+impl Clint for SoftClint {
+    // Room for the registers of 4095 harts.
+    const MSIP_END: u64 = CLINT_BASE + 0x3ffc;
+    const MTIMECMP_END: u64 = CLINT_BASE + 0xbff8;
+    type Msip = u32;
+    type Mtimecmp = u64;
+    type Mtime = u64;
+    type State = CsrFile;
+    type Exception = MemError;
+
+    // Advances mtime, and raises or clears the software and timer
+    // interrupts of the hart owning `csr`.
+    fn increment(&mut self, csr: &mut CsrFile) {
+        self.mtime = self.mtime.wrapping_add(1);
+        csr.time = self.mtime;
+        let hart = csr.hartid as usize;
+        let msip = self.msip.get(hart).is_some_and(|msip| msip & 1 != 0);
+        let mtip = self.mtimecmp.get(hart).is_some_and(|cmp| self.mtime >= *cmp);
+        csr.mip = (csr.mip & !(IRQ_MSI | IRQ_MTI))
+            | if msip { IRQ_MSI } else { 0 }
+            | if mtip { IRQ_MTI } else { 0 };
+    }
+
+    // Registers of missing harts read as zero.
+    fn read(&self, addr: u64, size: u8) -> Result<u64, Self::Exception> {
+        let Some((val, offset)) = self.register(addr) else {
+            return Ok(0);
+        };
+        let width = if (Self::MSIP_START..Self::MSIP_END).contains(&addr) { 4 } else { 8 };
+        check_access(offset, size, width)?;
+        match size {
+            WORD => Ok((val >> (offset * 8)) & 0xffff_ffff),
+            _ => Ok(val),
+        }
+    }
+
+    // Only bit 0 of msip is writable, writes to missing harts are ignored.
+    fn write(&mut self, addr: u64, value: u64, size: u8) -> Result<(), Self::Exception> {
+        let Some((old, offset)) = self.register(addr) else {
+            return Ok(());
+        };
+        let width = if (Self::MSIP_START..Self::MSIP_END).contains(&addr) { 4 } else { 8 };
+        check_access(offset, size, width)?;
+        let val = match size {
+            WORD => {
+                let shift = offset * 8;
+                (old & !(0xffff_ffff << shift)) | ((value & 0xffff_ffff) << shift)
+            },
+            _ => value,
+        };
+        match addr {
+            Self::MSIP_START..Self::MSIP_END => {
+                self.msip[((addr - Self::MSIP_START) / MSIP_STRIDE) as usize] = (val & 1) as u32;
+            },
+            Self::MTIMECMP_START..Self::MTIMECMP_END => {
+                self.mtimecmp[((addr - Self::MTIMECMP_START) / MTIMECMP_STRIDE) as usize] = val;
+            },
+            _ => self.mtime = val,
+        }
+        Ok(())
+    }
+}
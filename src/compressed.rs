@@ -0,0 +1,231 @@
+#![allow(unused, unused_mut, dead_code)]
+use crate::extensions::Base;
+use crate::instructions::Instruction;
+use crate::register::Register;
+
+// Length in bytes of a compressed instruction.
+pub const COMPRESSED_INST_LEN: u64 = 2u64;
+
+// The stack pointer and return address, implied by some compressed
+// instructions.
+const SP: usize = 2;
+const RA: usize = 1;
+
+// A parcel whose two low bits are not 0b11 is a 16 bit instruction.
+pub fn is_compressed(parcel: u16) -> bool {
+    parcel & 0b11 != 0b11
+}
+
+// Bits [hi:lo] of the parcel.
+fn bits(parcel: u16, hi: u32, lo: u32) -> u32 {
+    (u32::from(parcel) >> lo) & ((1 << (hi - lo + 1)) - 1)
+}
+
+// Sign extends the low `len` bits of an immediate.
+fn sext(imm: u32, len: u32) -> i32 {
+    ((imm << (32 - len)) as i32) >> (32 - len)
+}
+
+// The full 5 bit rd/rs1 field and rs2 field.
+fn rd(parcel: u16) -> Register {
+    (bits(parcel, 11, 7) as usize).into()
+}
+
+fn rs2(parcel: u16) -> Register {
+    (bits(parcel, 6, 2) as usize).into()
+}
+
+// The 3 bit register fields, which address x8-x15 (or f8-f15).
+fn rd_prime(parcel: u16) -> Register {
+    (bits(parcel, 4, 2) as usize + 8).into()
+}
+
+fn rs1_prime(parcel: u16) -> Register {
+    (bits(parcel, 9, 7) as usize + 8).into()
+}
+
+// 6 bit immediate of C.ADDI, C.LI, C.ANDI and C.ADDIW.
+fn ci_imm(parcel: u16) -> i32 {
+    sext((bits(parcel, 12, 12) << 5) | bits(parcel, 6, 2), 6)
+}
+
+fn ci_shamt(parcel: u16) -> u32 {
+    (bits(parcel, 12, 12) << 5) | bits(parcel, 6, 2)
+}
+
+// Offsets of word and doubleword loads and stores relative to a register.
+fn cl_word_offset(parcel: u16) -> i32 {
+    ((bits(parcel, 12, 10) << 3) | (bits(parcel, 6, 6) << 2) | (bits(parcel, 5, 5) << 6)) as i32
+}
+
+fn cl_double_offset(parcel: u16) -> i32 {
+    ((bits(parcel, 12, 10) << 3) | (bits(parcel, 6, 5) << 6)) as i32
+}
+
+// Offsets of word and doubleword loads relative to sp.
+fn ci_word_sp_offset(parcel: u16) -> i32 {
+    ((bits(parcel, 12, 12) << 5) | (bits(parcel, 6, 4) << 2) | (bits(parcel, 3, 2) << 6)) as i32
+}
+
+fn ci_double_sp_offset(parcel: u16) -> i32 {
+    ((bits(parcel, 12, 12) << 5) | (bits(parcel, 6, 5) << 3) | (bits(parcel, 4, 2) << 6)) as i32
+}
+
+// Offsets of word and doubleword stores relative to sp.
+fn css_word_sp_offset(parcel: u16) -> i32 {
+    ((bits(parcel, 12, 9) << 2) | (bits(parcel, 8, 7) << 6)) as i32
+}
+
+fn css_double_sp_offset(parcel: u16) -> i32 {
+    ((bits(parcel, 12, 10) << 3) | (bits(parcel, 9, 7) << 6)) as i32
+}
+
+// Jump offset of C.J and C.JAL.
+fn cj_offset(parcel: u16) -> i32 {
+    let imm = (bits(parcel, 12, 12) << 11)
+        | (bits(parcel, 11, 11) << 4)
+        | (bits(parcel, 10, 9) << 8)
+        | (bits(parcel, 8, 8) << 10)
+        | (bits(parcel, 7, 7) << 6)
+        | (bits(parcel, 6, 6) << 7)
+        | (bits(parcel, 5, 3) << 1)
+        | (bits(parcel, 2, 2) << 5);
+    sext(imm, 12)
+}
+
+// Branch offset of C.BEQZ and C.BNEZ.
+fn cb_offset(parcel: u16) -> i32 {
+    let imm = (bits(parcel, 12, 12) << 8)
+        | (bits(parcel, 11, 10) << 3)
+        | (bits(parcel, 6, 5) << 6)
+        | (bits(parcel, 4, 3) << 1)
+        | (bits(parcel, 2, 2) << 5);
+    sext(imm, 9)
+}
+
+/// Expands a 16 bit instruction into the 32 bit instruction it stands for.
+/// Reserved and illegal encodings, and those that do not exist on `base`,
+/// expand to `Instruction::Undefined`.
+pub fn expand(parcel: u16, base: Base) -> Instruction {
+    let x0 = Register::X0;
+    let sp: Register = SP.into();
+    let func3 = bits(parcel, 15, 13);
+    match (parcel & 0b11, func3) {
+        // An all zero parcel is defined to be illegal.
+        _ if parcel == 0 => Instruction::Undefined,
+        // C.ADDI4SPN
+        (0b00, 0b000) => {
+            let imm = (bits(parcel, 12, 11) << 4)
+                | (bits(parcel, 10, 7) << 6)
+                | (bits(parcel, 6, 6) << 2)
+                | (bits(parcel, 5, 5) << 3);
+            if imm == 0 {
+                return Instruction::Undefined;
+            }
+            Instruction::Addi { rd: rd_prime(parcel), rs1: sp, imm: imm as i32, func3: 0 }
+        },
+        // C.FLD
+        (0b00, 0b001) => Instruction::Fld { rd: rd_prime(parcel), rs1: rs1_prime(parcel), imm: cl_double_offset(parcel) },
+        // C.LW
+        (0b00, 0b010) => Instruction::Lw { rd: rd_prime(parcel), rs1: rs1_prime(parcel), imm: cl_word_offset(parcel), func3: 0b010 },
+        // C.FLW on RV32, C.LD on RV64
+        (0b00, 0b011) => match base {
+            Base::I32 => Instruction::Flw { rd: rd_prime(parcel), rs1: rs1_prime(parcel), imm: cl_word_offset(parcel) },
+            Base::I64 => Instruction::Ld { rd: rd_prime(parcel), rs1: rs1_prime(parcel), imm: cl_double_offset(parcel), func3: 0b011 },
+        },
+        // C.FSD
+        (0b00, 0b101) => Instruction::Fsd { rs1: rs1_prime(parcel), rs2: rd_prime(parcel), imm: cl_double_offset(parcel) },
+        // C.SW
+        (0b00, 0b110) => Instruction::Sw { rs1: rs1_prime(parcel), rs2: rd_prime(parcel), imm: cl_word_offset(parcel), func3: 0b010 },
+        // C.FSW on RV32, C.SD on RV64
+        (0b00, 0b111) => match base {
+            Base::I32 => Instruction::Fsw { rs1: rs1_prime(parcel), rs2: rd_prime(parcel), imm: cl_word_offset(parcel) },
+            Base::I64 => Instruction::Sd { rs1: rs1_prime(parcel), rs2: rd_prime(parcel), imm: cl_double_offset(parcel), func3: 0b011 },
+        },
+        // C.ADDI, C.NOP when rd is x0
+        (0b01, 0b000) => Instruction::Addi { rd: rd(parcel), rs1: rd(parcel), imm: ci_imm(parcel), func3: 0 },
+        // C.JAL on RV32, C.ADDIW on RV64
+        (0b01, 0b001) => match base {
+            Base::I32 => Instruction::Jal { rd: RA.into(), imm: cj_offset(parcel) },
+            Base::I64 if rd(parcel) == x0 => Instruction::Undefined,
+            Base::I64 => Instruction::Addiw { rd: rd(parcel), rs1: rd(parcel), imm: ci_imm(parcel), func3: 0 },
+        },
+        // C.LI
+        (0b01, 0b010) => Instruction::Addi { rd: rd(parcel), rs1: x0, imm: ci_imm(parcel), func3: 0 },
+        // C.ADDI16SP when rd is sp, C.LUI otherwise
+        (0b01, 0b011) => {
+            if bits(parcel, 11, 7) as usize == SP {
+                let imm = (bits(parcel, 12, 12) << 9)
+                    | (bits(parcel, 6, 6) << 4)
+                    | (bits(parcel, 5, 5) << 6)
+                    | (bits(parcel, 4, 3) << 7)
+                    | (bits(parcel, 2, 2) << 5);
+                if imm == 0 {
+                    return Instruction::Undefined;
+                }
+                Instruction::Addi { rd: sp, rs1: sp, imm: sext(imm, 10), func3: 0 }
+            } else {
+                let imm = (bits(parcel, 12, 12) << 17) | (bits(parcel, 6, 2) << 12);
+                if imm == 0 {
+                    return Instruction::Undefined;
+                }
+                Instruction::Lui { rd: rd(parcel), imm: sext(imm, 18) }
+            }
+        },
+        // C.SRLI, C.SRAI, C.ANDI and the register-register ALU operations
+        (0b01, 0b100) => {
+            let rd = rs1_prime(parcel);
+            let rs2 = rd_prime(parcel);
+            match (bits(parcel, 11, 10), bits(parcel, 12, 12), bits(parcel, 6, 5)) {
+                (0b00, _, _) => Instruction::Srli { rd, rs1: rd, shamt: ci_shamt(parcel), func3: 0b101, func7: 0 },
+                (0b01, _, _) => Instruction::Srai { rd, rs1: rd, shamt: ci_shamt(parcel), func3: 0b101, func7: 0b0100000 },
+                (0b10, _, _) => Instruction::Andi { rd, rs1: rd, imm: ci_imm(parcel), func3: 0b111 },
+                (_, 0, 0b00) => Instruction::Sub { rd, rs1: rd, rs2, func3: 0, func7: 0b0100000 },
+                (_, 0, 0b01) => Instruction::Xor { rd, rs1: rd, rs2, func3: 0b100, func7: 0 },
+                (_, 0, 0b10) => Instruction::Or { rd, rs1: rd, rs2, func3: 0b110, func7: 0 },
+                (_, 0, _) => Instruction::And { rd, rs1: rd, rs2, func3: 0b111, func7: 0 },
+                (_, _, 0b00) if base == Base::I64 => Instruction::Subw { rd, rs1: rd, rs2, func3: 0, func7: 0b0100000 },
+                (_, _, 0b01) if base == Base::I64 => Instruction::Addw { rd, rs1: rd, rs2, func3: 0, func7: 0 },
+                _ => Instruction::Undefined,
+            }
+        },
+        // C.J
+        (0b01, 0b101) => Instruction::Jal { rd: x0, imm: cj_offset(parcel) },
+        // C.BEQZ
+        (0b01, 0b110) => Instruction::Beq { rd: x0, rs1: rs1_prime(parcel), rs2: x0, imm: cb_offset(parcel), func3: 0 },
+        // C.BNEZ
+        (0b01, 0b111) => Instruction::Bne { rd: x0, rs1: rs1_prime(parcel), rs2: x0, imm: cb_offset(parcel), func3: 0b001 },
+        // C.SLLI
+        (0b10, 0b000) => Instruction::Slli { rd: rd(parcel), rs1: rd(parcel), shamt: ci_shamt(parcel), func3: 0b001, func7: 0 },
+        // C.FLDSP
+        (0b10, 0b001) => Instruction::Fld { rd: rd(parcel), rs1: sp, imm: ci_double_sp_offset(parcel) },
+        // C.LWSP
+        (0b10, 0b010) if rd(parcel) == x0 => Instruction::Undefined,
+        (0b10, 0b010) => Instruction::Lw { rd: rd(parcel), rs1: sp, imm: ci_word_sp_offset(parcel), func3: 0b010 },
+        // C.FLWSP on RV32, C.LDSP on RV64
+        (0b10, 0b011) => match base {
+            Base::I32 => Instruction::Flw { rd: rd(parcel), rs1: sp, imm: ci_word_sp_offset(parcel) },
+            Base::I64 if rd(parcel) == x0 => Instruction::Undefined,
+            Base::I64 => Instruction::Ld { rd: rd(parcel), rs1: sp, imm: ci_double_sp_offset(parcel), func3: 0b011 },
+        },
+        // C.JR, C.MV, C.EBREAK, C.JALR and C.ADD
+        (0b10, 0b100) => match (bits(parcel, 12, 12), rd(parcel), rs2(parcel)) {
+            (0, Register::X0, Register::X0) => Instruction::Undefined,
+            (0, rs1, Register::X0) => Instruction::Jalr { rd: x0, rs1, imm: 0 },
+            (0, rd, rs2) => Instruction::Add { rd, rs1: x0, rs2, func3: 0, func7: 0 },
+            (_, Register::X0, Register::X0) => Instruction::EBreak,
+            (_, rs1, Register::X0) => Instruction::Jalr { rd: RA.into(), rs1, imm: 0 },
+            (_, rd, rs2) => Instruction::Add { rd, rs1: rd, rs2, func3: 0, func7: 0 },
+        },
+        // C.FSDSP
+        (0b10, 0b101) => Instruction::Fsd { rs1: sp, rs2: rs2(parcel), imm: css_double_sp_offset(parcel) },
+        // C.SWSP
+        (0b10, 0b110) => Instruction::Sw { rs1: sp, rs2: rs2(parcel), imm: css_word_sp_offset(parcel), func3: 0b010 },
+        // C.FSWSP on RV32, C.SDSP on RV64
+        (0b10, 0b111) => match base {
+            Base::I32 => Instruction::Fsw { rs1: sp, rs2: rs2(parcel), imm: css_word_sp_offset(parcel) },
+            Base::I64 => Instruction::Sd { rs1: sp, rs2: rs2(parcel), imm: css_double_sp_offset(parcel), func3: 0b011 },
+        },
+        _ => Instruction::Undefined,
+    }
+}
@@ -3,6 +3,7 @@ pub const INDEX_SIZE: usize = 1 << INDEX_SHIFTS;
 pub const N_REGISTERS: usize = 32;
 pub const MAX_MEM: usize = 4 << 20;
 pub const STACK_SIZE: usize = 1 << 20;
+pub const HEAP_SIZE: usize = 1 << 20;
 pub const INDICES: usize = MAX_MEM / INDEX_SIZE;
 pub const FRAME_SHIFTS: usize = 18;
 pub const FRAME_SIZE: usize = 1 << FRAME_SHIFTS;
@@ -0,0 +1,410 @@
+use std::error::Error;
+use std::fmt;
+use crate::pmp::Pmp;
+
+// Floating point
+pub const FFLAGS: usize = 0x001;
+pub const FRM: usize = 0x002;
+pub const FCSR: usize = 0x003;
+
+// Counters and timers
+pub const CYCLE: usize = 0xc00;
+pub const TIME: usize = 0xc01;
+pub const INSTRET: usize = 0xc02;
+pub const CYCLEH: usize = 0xc80;
+pub const TIMEH: usize = 0xc81;
+pub const INSTRETH: usize = 0xc82;
+
+// Supervisor trap setup
+pub const SSTATUS: usize = 0x100;
+pub const SIE: usize = 0x104;
+pub const STVEC: usize = 0x105;
+
+// Supervisor trap handling
+pub const SSCRATCH: usize = 0x140;
+pub const SEPC: usize = 0x141;
+pub const SCAUSE: usize = 0x142;
+pub const STVAL: usize = 0x143;
+pub const SIP: usize = 0x144;
+
+// Supervisor protection and translation
+pub const SATP: usize = 0x180;
+
+// Machine trap setup
+pub const MSTATUS: usize = 0x300;
+pub const MISA: usize = 0x301;
+pub const MEDELEG: usize = 0x302;
+pub const MIDELEG: usize = 0x303;
+pub const MIE: usize = 0x304;
+pub const MTVEC: usize = 0x305;
+
+// Machine trap handling
+pub const MSCRATCH: usize = 0x340;
+pub const MEPC: usize = 0x341;
+pub const MCAUSE: usize = 0x342;
+pub const MTVAL: usize = 0x343;
+pub const MIP: usize = 0x344;
+
+// Machine memory protection
+pub const PMPCFG0: usize = 0x3a0;
+pub const PMPCFG15: usize = 0x3af;
+pub const PMPADDR0: usize = 0x3b0;
+pub const PMPADDR63: usize = 0x3ef;
+
+// Machine counters
+pub const MCYCLE: usize = 0xb00;
+pub const MINSTRET: usize = 0xb02;
+pub const MCYCLEH: usize = 0xb80;
+pub const MINSTRETH: usize = 0xb82;
+
+// Machine information
+pub const MVENDORID: usize = 0xf11;
+pub const MARCHID: usize = 0xf12;
+pub const MIMPID: usize = 0xf13;
+pub const MHARTID: usize = 0xf14;
+
+// mstatus fields
+pub const MSTATUS_SIE: u64 = 1 << 1;
+pub const MSTATUS_MIE: u64 = 1 << 3;
+pub const MSTATUS_SPIE: u64 = 1 << 5;
+pub const MSTATUS_MPIE: u64 = 1 << 7;
+pub const MSTATUS_SPP: u64 = 1 << 8;
+pub const MSTATUS_MPP_SHIFT: u64 = 11;
+pub const MSTATUS_MPP: u64 = 0b11 << MSTATUS_MPP_SHIFT;
+pub const MSTATUS_MPRV: u64 = 1 << 17;
+pub const MSTATUS_SUM: u64 = 1 << 18;
+pub const MSTATUS_MXR: u64 = 1 << 19;
+pub const MSTATUS_TVM: u64 = 1 << 20;
+pub const MSTATUS_TSR: u64 = 1 << 22;
+pub const MSTATUS_UXL: u64 = 0b11 << 32;
+pub const MSTATUS_SXL: u64 = 0b11 << 34;
+
+// The mstatus fields software can write.
+pub const MSTATUS_MASK: u64 = MSTATUS_SIE
+    | MSTATUS_MIE
+    | MSTATUS_SPIE
+    | MSTATUS_MPIE
+    | MSTATUS_SPP
+    | MSTATUS_MPP
+    | MSTATUS_MPRV
+    | MSTATUS_SUM
+    | MSTATUS_MXR
+    | MSTATUS_TVM
+    | MSTATUS_TSR;
+
+// The mstatus fields visible through sstatus.
+pub const SSTATUS_MASK: u64 = MSTATUS_SIE | MSTATUS_SPIE | MSTATUS_SPP | MSTATUS_SUM | MSTATUS_MXR;
+
+// Interrupt pending/enable bits.
+pub const IRQ_SSI: u64 = 1 << 1;
+pub const IRQ_MSI: u64 = 1 << 3;
+pub const IRQ_STI: u64 = 1 << 5;
+pub const IRQ_MTI: u64 = 1 << 7;
+pub const IRQ_SEI: u64 = 1 << 9;
+pub const IRQ_MEI: u64 = 1 << 11;
+
+// The interrupts this hart implements, and the ones software may raise
+// through mip. The machine level bits of mip are driven by devices.
+pub const MIE_MASK: u64 = IRQ_SSI | IRQ_MSI | IRQ_STI | IRQ_MTI | IRQ_SEI | IRQ_MEI;
+pub const MIP_MASK: u64 = IRQ_SSI | IRQ_STI | IRQ_SEI;
+
+// Interrupts that can be delegated to supervisor mode, and the one
+// supervisor software may raise through sip.
+pub const MIDELEG_MASK: u64 = IRQ_SSI | IRQ_STI | IRQ_SEI;
+pub const SIP_MASK: u64 = IRQ_SSI;
+
+// Interrupt causes in the order they are taken when several are pending.
+pub const IRQ_PRIORITY: [u64; 6] = [11, 3, 7, 9, 1, 5];
+
+// Exceptions that can be delegated to supervisor mode, all but the
+// environment call from machine mode and the reserved causes.
+pub const MEDELEG_MASK: u64 = 0xb3ff;
+
+pub const FFLAGS_MASK: u64 = 0x1f;
+pub const FRM_MASK: u64 = 0x7;
+pub const FRM_SHIFT: u64 = 5;
+
+// satp fields on RV64, only the Sv39 and Sv48 translation modes are
+// implemented. RV32 harts only support bare mode.
+pub const SATP_MODE_SHIFT: u64 = 60;
+pub const SATP_MODE_BARE: u64 = 0;
+pub const SATP_MODE_SV39: u64 = 8;
+pub const SATP_MODE_SV48: u64 = 9;
+pub const SATP_ASID_SHIFT: u64 = 44;
+pub const SATP_ASID_MASK: u64 = 0xffff;
+pub const SATP_PPN_MASK: u64 = (1 << 44) - 1;
+
+/// The privilege level a hart runs at.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Privilege {
+    User = 0,
+    Supervisor = 1,
+    Machine = 3,
+}
+
+impl Privilege {
+    // Decodes a privilege field such as mstatus.MPP. The reserved
+    // encoding 2 is read as user mode.
+    pub fn from_bits(bits: u64) -> Privilege {
+        match bits & 0b11 {
+            3 => Privilege::Machine,
+            1 => Privilege::Supervisor,
+            _ => Privilege::User,
+        }
+    }
+}
+
+// The lowest privilege that can access a CSR, from bits 9:8 of its address.
+pub fn csr_privilege(addr: usize) -> u64 {
+    ((addr >> 8) & 0b11) as u64
+}
+
+// CSRs with both of the address bits 11:10 set are read-only.
+pub fn csr_read_only(addr: usize) -> bool {
+    (addr >> 10) & 0b11 == 0b11
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum CsrError {
+    // There is no CSR at the address.
+    NonExistent(usize),
+    // The CSR needs a higher privilege than the hart is running at.
+    Privileged(usize),
+    // The CSR is read-only.
+    ReadOnly(usize),
+}
+
+impl fmt::Display for CsrError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for CsrError {}
+
+/// The control and status registers of a hart. Registers are kept at 64
+/// bits; on an RV32 hart the upper halves of the counters are reached
+/// through the `h` CSRs. Writes only change the bits software may write,
+/// the other bits keep their value.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct CsrFile {
+    pub mstatus: u64,
+    pub misa: u64,
+    pub medeleg: u64,
+    pub mideleg: u64,
+    pub mie: u64,
+    pub mip: u64,
+    pub mtvec: u64,
+    pub mscratch: u64,
+    pub mepc: u64,
+    pub mcause: u64,
+    pub mtval: u64,
+    pub stvec: u64,
+    pub sscratch: u64,
+    pub sepc: u64,
+    pub scause: u64,
+    pub stval: u64,
+    pub satp: u64,
+    pub cycle: u64,
+    pub instret: u64,
+    pub time: u64,
+    pub fflags: u64,
+    pub frm: u64,
+    pub pmp: Pmp,
+    pub hartid: u64,
+    xlen: u32,
+}
+
+impl CsrFile {
+    pub fn new(misa: u64) -> CsrFile {
+        // The xlen is in the top two bits of misa.
+        let xlen = if misa >> 62 == 2 { 64 } else { 32 };
+        CsrFile {
+            misa,
+            xlen,
+            ..CsrFile::default()
+        }
+    }
+
+    pub fn xlen(&self) -> u32 {
+        self.xlen
+    }
+
+    // Advances the counters by one instruction, instret only counts
+    // instructions that completed. Time is left alone when a device keeps
+    // it, as the CLINT does with mtime.
+    pub fn tick(&mut self, retired: bool, keeps_time: bool) {
+        self.cycle = self.cycle.wrapping_add(1);
+        if !keeps_time {
+            self.time = self.time.wrapping_add(1);
+        }
+        if retired {
+            self.instret = self.instret.wrapping_add(1);
+        }
+    }
+
+    // The interrupt to take before the next instruction at `privilege`, and
+    // whether it is delegated to supervisor mode. Machine interrupts are
+    // taken below machine mode or when mstatus.MIE is set, delegated ones
+    // below supervisor mode or in it when mstatus.SIE is set.
+    pub fn pending_interrupt(&self, privilege: Privilege) -> Option<(u64, bool)> {
+        let pending = self.mip & self.mie;
+        if pending == 0 {
+            return None;
+        }
+        let machine = privilege < Privilege::Machine || self.mstatus & MSTATUS_MIE != 0;
+        let supervisor = privilege < Privilege::Supervisor
+            || (privilege == Privilege::Supervisor && self.mstatus & MSTATUS_SIE != 0);
+        let highest = |bits: u64| IRQ_PRIORITY.into_iter().find(|cause| (bits >> cause) & 1 == 1);
+        match highest(pending & !self.mideleg) {
+            Some(cause) if machine => Some((cause, false)),
+            _ => highest(pending & self.mideleg).filter(|_| supervisor).map(|cause| (cause, true)),
+        }
+    }
+
+    // Checks the CSR exists and can be accessed at the privilege.
+    fn check(&self, addr: usize, privilege: Privilege) -> Result<(), CsrError> {
+        let exists = match addr {
+            CYCLEH | TIMEH | INSTRETH | MCYCLEH | MINSTRETH => self.xlen == 32,
+            // RV64 packs eight entries in each even pmpcfg.
+            PMPCFG0..=PMPCFG15 => self.xlen == 32 || addr.is_multiple_of(2),
+            PMPADDR0..=PMPADDR63 => true,
+            FFLAGS | FRM | FCSR | CYCLE | TIME | INSTRET | SSTATUS | SIE | STVEC | SSCRATCH | SEPC | SCAUSE
+            | STVAL | SIP | SATP | MSTATUS | MISA | MEDELEG | MIDELEG | MIE | MTVEC | MSCRATCH | MEPC | MCAUSE
+            | MTVAL | MIP | MCYCLE | MINSTRET | MVENDORID | MARCHID | MIMPID | MHARTID => true,
+            _ => false,
+        };
+        if !exists {
+            return Err(CsrError::NonExistent(addr));
+        }
+        if (privilege as u64) < csr_privilege(addr) {
+            return Err(CsrError::Privileged(addr));
+        }
+        // mstatus.TVM traps supervisor accesses to satp.
+        if addr == SATP && privilege == Privilege::Supervisor && self.mstatus & MSTATUS_TVM != 0 {
+            return Err(CsrError::Privileged(addr));
+        }
+        Ok(())
+    }
+
+    pub fn read(&self, addr: usize, privilege: Privilege) -> Result<u64, CsrError> {
+        self.check(addr, privilege)?;
+        let val = match addr {
+            FFLAGS => self.fflags,
+            FRM => self.frm,
+            FCSR => (self.frm << FRM_SHIFT) | self.fflags,
+            CYCLE | MCYCLE => self.cycle,
+            TIME => self.time,
+            INSTRET | MINSTRET => self.instret,
+            CYCLEH | MCYCLEH => self.cycle >> 32,
+            TIMEH => self.time >> 32,
+            INSTRETH | MINSTRETH => self.instret >> 32,
+            SSTATUS => self.mstatus & (SSTATUS_MASK | self.xl()),
+            SIE => self.mie & self.mideleg,
+            STVEC => self.stvec,
+            SSCRATCH => self.sscratch,
+            SEPC => self.sepc,
+            SCAUSE => self.scause,
+            STVAL => self.stval,
+            SIP => self.mip & self.mideleg,
+            SATP => self.satp,
+            MSTATUS => self.mstatus | self.xl(),
+            MISA => self.misa,
+            MEDELEG => self.medeleg,
+            MIDELEG => self.mideleg,
+            MIE => self.mie,
+            MIP => self.mip,
+            MTVEC => self.mtvec,
+            MSCRATCH => self.mscratch,
+            MEPC => self.mepc,
+            MCAUSE => self.mcause,
+            MTVAL => self.mtval,
+            MHARTID => self.hartid,
+            PMPCFG0..=PMPCFG15 => self.pmp.read_cfg(addr - PMPCFG0, self.xlen),
+            PMPADDR0..=PMPADDR63 => self.pmp.read_addr(addr - PMPADDR0),
+            _ => 0,
+        };
+        Ok(self.truncate(val))
+    }
+
+    pub fn write(&mut self, addr: usize, val: u64, privilege: Privilege) -> Result<(), CsrError> {
+        self.check(addr, privilege)?;
+        if csr_read_only(addr) {
+            return Err(CsrError::ReadOnly(addr));
+        }
+        let val = self.truncate(val);
+        match addr {
+            FFLAGS => self.fflags = val & FFLAGS_MASK,
+            FRM => self.frm = val & FRM_MASK,
+            FCSR => {
+                self.fflags = val & FFLAGS_MASK;
+                self.frm = (val >> FRM_SHIFT) & FRM_MASK;
+            },
+            MCYCLE if self.xlen == 32 => self.cycle = (self.cycle & !0xffff_ffff) | val,
+            MINSTRET if self.xlen == 32 => self.instret = (self.instret & !0xffff_ffff) | val,
+            MCYCLE => self.cycle = val,
+            MINSTRET => self.instret = val,
+            MCYCLEH => self.cycle = (self.cycle & 0xffff_ffff) | (val << 32),
+            MINSTRETH => self.instret = (self.instret & 0xffff_ffff) | (val << 32),
+            SSTATUS => self.mstatus = (self.mstatus & !SSTATUS_MASK) | (val & SSTATUS_MASK),
+            // sie and sip only reach the delegated interrupts.
+            SIE => self.mie = (self.mie & !self.mideleg) | (val & self.mideleg),
+            // The vector mode is 0 (direct) or 1 (vectored), bit 1 is reserved.
+            STVEC => self.stvec = val & !0b10,
+            SSCRATCH => self.sscratch = val,
+            SEPC => self.sepc = val & !1,
+            SCAUSE => self.scause = val,
+            STVAL => self.stval = val,
+            SIP => {
+                let mask = SIP_MASK & self.mideleg;
+                self.mip = (self.mip & !mask) | (val & mask);
+            },
+            // Writes selecting an unsupported translation mode are ignored.
+            SATP => match val >> SATP_MODE_SHIFT {
+                SATP_MODE_BARE | SATP_MODE_SV39 | SATP_MODE_SV48 if self.xlen == 64 => self.satp = val,
+                _ if self.xlen == 32 && val >> 31 == 0 => self.satp = val,
+                _ => {},
+            },
+            MSTATUS => {
+                let mut status = (self.mstatus & !MSTATUS_MASK) | (val & MSTATUS_MASK);
+                // MPP can't hold the reserved privilege 2.
+                if (status & MSTATUS_MPP) >> MSTATUS_MPP_SHIFT == 2 {
+                    status = (status & !MSTATUS_MPP) | (self.mstatus & MSTATUS_MPP);
+                }
+                self.mstatus = status;
+            },
+            // The extensions are fixed by the encoding table.
+            MISA => {},
+            MEDELEG => self.medeleg = val & MEDELEG_MASK,
+            MIDELEG => self.mideleg = val & MIDELEG_MASK,
+            MIE => self.mie = val & MIE_MASK,
+            MIP => self.mip = (self.mip & !MIP_MASK) | (val & MIP_MASK),
+            MTVEC => self.mtvec = val & !0b10,
+            MSCRATCH => self.mscratch = val,
+            MEPC => self.mepc = val & !1,
+            MCAUSE => self.mcause = val,
+            MTVAL => self.mtval = val,
+            PMPCFG0..=PMPCFG15 => self.pmp.write_cfg(addr - PMPCFG0, val, self.xlen),
+            PMPADDR0..=PMPADDR63 => self.pmp.write_addr(addr - PMPADDR0, val, self.xlen),
+            _ => {},
+        }
+        Ok(())
+    }
+
+    // mstatus.UXL and SXL are fixed to 64 bits on an RV64 hart.
+    fn xl(&self) -> u64 {
+        if self.xlen == 64 {
+            (2 << 32) | (2 << 34)
+        } else {
+            0
+        }
+    }
+
+    fn truncate(&self, val: u64) -> u64 {
+        if self.xlen == 32 {
+            val & 0xffff_ffff
+        } else {
+            val
+        }
+    }
+}
@@ -0,0 +1,166 @@
+use std::fmt::{Display, Formatter};
+use std::error::Error;
+
+pub const ELF_MAGIC: [u8; 4] = [0x7f, b'E', b'L', b'F'];
+pub const ELFCLASS32: u8 = 1;
+pub const ELFCLASS64: u8 = 2;
+pub const ELFDATA2LSB: u8 = 1;
+pub const EM_RISCV: u16 = 243;
+pub const PT_LOAD: u32 = 1;
+pub const PF_X: u32 = 0b001;
+pub const PF_W: u32 = 0b010;
+pub const PF_R: u32 = 0b100;
+
+const ELF32_EHDR_SIZE: usize = 52;
+const ELF64_EHDR_SIZE: usize = 64;
+const ELF32_PHDR_SIZE: usize = 32;
+const ELF64_PHDR_SIZE: usize = 56;
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ElfError {
+    Truncated,
+    BadMagic,
+    UnsupportedClass(u8),
+    UnsupportedEncoding(u8),
+    ClassMismatch,
+    MachineMismatch(u16),
+    SegmentOutOfBounds,
+    // A segment that is both writable and executable.
+    WritableCode,
+    // A page holding both code and data.
+    SharedCodePage,
+    // Segments taking more memory, in bytes, than there is room for code.
+    Oversized(u64),
+}
+
+impl Display for ElfError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for ElfError {}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct ProgramHeader {
+    pub p_type: u32,
+    pub flags: u32,
+    pub offset: u64,
+    pub vaddr: u64,
+    pub filesz: u64,
+    pub memsz: u64,
+}
+
+// The parts of an ELF image the loader needs: the file class, the target
+// machine, the entry point and the program headers.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Elf {
+    pub class: u8,
+    pub machine: u16,
+    pub entry: u64,
+    pub headers: Vec<ProgramHeader>,
+}
+
+impl Elf {
+    pub fn parse(bytes: &[u8]) -> Result<Elf, ElfError> {
+        if bytes.len() < 16 {
+            return Err(ElfError::Truncated);
+        }
+        if bytes[0..4] != ELF_MAGIC {
+            return Err(ElfError::BadMagic);
+        }
+        let class = bytes[4];
+        if bytes[5] != ELFDATA2LSB {
+            return Err(ElfError::UnsupportedEncoding(bytes[5]));
+        }
+
+        let (entry, phoff, phentsize, phnum) = match class {
+            ELFCLASS32 => {
+                if bytes.len() < ELF32_EHDR_SIZE {
+                    return Err(ElfError::Truncated);
+                }
+                (read_u32(bytes, 24)? as u64, read_u32(bytes, 28)? as u64, read_u16(bytes, 42)?, read_u16(bytes, 44)?)
+            },
+            ELFCLASS64 => {
+                if bytes.len() < ELF64_EHDR_SIZE {
+                    return Err(ElfError::Truncated);
+                }
+                (read_u64(bytes, 24)?, read_u64(bytes, 32)?, read_u16(bytes, 54)?, read_u16(bytes, 56)?)
+            },
+            _ => return Err(ElfError::UnsupportedClass(class)),
+        };
+        let machine = read_u16(bytes, 18)?;
+
+        let min_phentsize = if class == ELFCLASS64 { ELF64_PHDR_SIZE } else { ELF32_PHDR_SIZE };
+        if phnum > 0 && (phentsize as usize) < min_phentsize {
+            return Err(ElfError::Truncated);
+        }
+
+        let mut headers = Vec::with_capacity(phnum as usize);
+        for i in 0..phnum as u64 {
+            let start = phoff
+                .checked_add(i * phentsize as u64)
+                .ok_or(ElfError::Truncated)? as usize;
+            if start.checked_add(min_phentsize).is_none_or(|end| end > bytes.len()) {
+                return Err(ElfError::Truncated);
+            }
+            let header = match class {
+                ELFCLASS32 => ProgramHeader {
+                    p_type: read_u32(bytes, start)?,
+                    offset: read_u32(bytes, start + 4)? as u64,
+                    vaddr: read_u32(bytes, start + 8)? as u64,
+                    filesz: read_u32(bytes, start + 16)? as u64,
+                    memsz: read_u32(bytes, start + 20)? as u64,
+                    flags: read_u32(bytes, start + 24)?,
+                },
+                _ => ProgramHeader {
+                    p_type: read_u32(bytes, start)?,
+                    flags: read_u32(bytes, start + 4)?,
+                    offset: read_u64(bytes, start + 8)?,
+                    vaddr: read_u64(bytes, start + 16)?,
+                    filesz: read_u64(bytes, start + 32)?,
+                    memsz: read_u64(bytes, start + 40)?,
+                },
+            };
+            headers.push(header);
+        }
+
+        Ok(Elf { class, machine, entry, headers })
+    }
+
+    pub fn loadable(&self) -> impl Iterator<Item = &ProgramHeader> {
+        self.headers.iter().filter(|ph| ph.p_type == PT_LOAD)
+    }
+}
+
+impl ProgramHeader {
+    // The bytes of the segment that are backed by the file.
+    pub fn data<'a>(&self, bytes: &'a [u8]) -> Result<&'a [u8], ElfError> {
+        if self.filesz > self.memsz {
+            return Err(ElfError::SegmentOutOfBounds);
+        }
+        let start = self.offset as usize;
+        let end = self.offset
+            .checked_add(self.filesz)
+            .ok_or(ElfError::SegmentOutOfBounds)? as usize;
+        bytes.get(start..end).ok_or(ElfError::SegmentOutOfBounds)
+    }
+}
+
+fn read_u16(bytes: &[u8], at: usize) -> Result<u16, ElfError> {
+    let end = at.checked_add(2).ok_or(ElfError::Truncated)?;
+    let b = bytes.get(at..end).ok_or(ElfError::Truncated)?;
+    Ok(u16::from_le_bytes([b[0], b[1]]))
+}
+
+fn read_u32(bytes: &[u8], at: usize) -> Result<u32, ElfError> {
+    let end = at.checked_add(4).ok_or(ElfError::Truncated)?;
+    let b = bytes.get(at..end).ok_or(ElfError::Truncated)?;
+    Ok(u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn read_u64(bytes: &[u8], at: usize) -> Result<u64, ElfError> {
+    let end = at.checked_add(8).ok_or(ElfError::Truncated)?;
+    let b = bytes.get(at..end).ok_or(ElfError::Truncated)?;
+    Ok(u64::from_le_bytes([b[0], b[1], b[2], b[3], b[4], b[5], b[6], b[7]]))
+}
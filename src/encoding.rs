@@ -1,12 +1,14 @@
 #![allow(unused, unused_mut, dead_code)]
-use crate::extensions::{Base, Extension};
+use crate::extensions::{Base, Extension, ExtensionSet};
 use crate::encoding_types::*;
+use crate::instructions::Instruction;
+use strum::EnumProperty;
 
 // S struct for fast OpCode lookups
 #[derive(Clone, Debug)]
 pub struct EncodingTable {
     table: [OpCodeType; 128],
-    ext: Extension,
+    extensions: ExtensionSet,
     base: Base,
 }
 
@@ -23,10 +25,10 @@ pub enum OpCodeType {
 }
 
 impl EncodingTable {
-    pub fn new(ext: Extension, base: Base) -> EncodingTable {
+    pub fn new<E: Into<ExtensionSet>>(extensions: E, base: Base) -> EncodingTable {
         EncodingTable {
             table: TYPE_TABLE,
-            ext,
+            extensions: extensions.into(),
             base,
         }
     }
@@ -36,13 +38,31 @@ impl EncodingTable {
         self.table[opcode as usize]
     }
 
-    pub fn get_ext(&self) -> Extension {
-        self.ext
+    pub fn get_extensions(&self) -> &ExtensionSet {
+        &self.extensions
     }
 
     pub fn get_base(&self) -> Base {
         self.base
     }
+
+    // Whether 16 bit instructions can be fetched and decoded.
+    pub fn compressed(&self) -> bool {
+        self.extensions.contains("c")
+    }
+
+    pub fn misa(&self) -> u64 {
+        self.extensions.misa(self.base)
+    }
+
+    // Whether the instruction exists on this base and its extension is
+    // in the set.
+    pub fn supports(&self, instruction: &Instruction) -> bool {
+        if self.base == Base::I32 && instruction.get_str("Base") == Some("64") {
+            return false;
+        }
+        instruction.get_str("Ext").is_some_and(|ext| self.extensions.contains(ext))
+    }
 }
 
 impl From<Inst> for OpCodeType {
@@ -77,9 +97,11 @@ impl From<OpCodeType> for OpCode {
 impl Default for EncodingTable {
     fn default() -> EncodingTable {
         let table: [OpCodeType; 128] = TYPE_TABLE;
+        // The soft thread also implements Q, so the default hart accepts
+        // it on top of G.
         EncodingTable {
             table,
-            ext: Extension::G,
+            extensions: ExtensionSet::from(Extension::G).with("q"),
             base: Base::I64
         }
     }
@@ -344,6 +366,7 @@ impl From<Inst> for Unpacked {
             },
             OpCodeType::I => {
                 let mut unpacked = Unpacked::default();
+                let imm = (imm << 20) >> 20;
                 unpacked.opcode = opcode;
                 unpacked.imm = Some(imm);
                 unpacked.rs1 = Some(rs1);
@@ -355,7 +378,7 @@ impl From<Inst> for Unpacked {
             }
             OpCodeType::S => { 
                 let mut unpacked = Unpacked::default();
-                let imm = (imm_115 | imm_4) as i32;
+                let imm = (((imm_115 << 5) | imm_4) << 20) >> 20;
                 unpacked.opcode = opcode;
                 unpacked.imm = Some(imm);
                 unpacked.rs1 = Some(rs1);
@@ -388,7 +411,7 @@ impl From<Inst> for Unpacked {
                 
                 // Create pieces of immediate;
                 let imm20 = ((imm >> 19) & 1) as i32;
-                let imm101 = ((imm >> 0) & 0b1111111111) as i32;
+                let imm101 = ((imm >> 9) & 0b1111111111) as i32;
                 let imm11 = ((imm >> 8) & 1) as i32;
                 let imm1912 = ((imm >> 0) & 0b11111111) as i32;
                 // Combine immediate
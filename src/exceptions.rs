@@ -1,4 +1,5 @@
 use std::error::Error;
+use crate::elf::ElfError;
 use std::fmt::{Display, Formatter, Result};
 
 #[derive(Debug, PartialEq)]
@@ -20,6 +21,9 @@ pub enum Exception {
     StackSizeExceeded,
     InvalidAddr,
     LoadFromBuffer,
+    OutOfGas(u64),
+    InvalidElf(ElfError),
+    State(String),
     General,
 }
 
@@ -31,6 +35,36 @@ pub enum Trap {
     Fatal
 }
 
+impl Exception {
+    // The exception code written to mcause when the exception is taken
+    // as a trap. Errors that only exist on the host side have no code.
+    pub fn code(&self) -> Option<u64> {
+        match self {
+            Exception::AddressMisaligned => Some(0),
+            Exception::AccessFault => Some(1),
+            Exception::Invalid(_) => Some(2),
+            Exception::Breakpoint => Some(3),
+            Exception::LoadAddressMisaligned => Some(4),
+            Exception::LoadAccessFault => Some(5),
+            Exception::StoreAMOAddressMisaligned => Some(6),
+            Exception::StoreAMOAccessFault => Some(7),
+            Exception::EnvironmentCallFromUMode => Some(8),
+            Exception::EnvironmentCallFromSMode => Some(9),
+            Exception::EnvironmentCallFromMMode => Some(11),
+            Exception::InstructionPageFault(_) => Some(12),
+            Exception::LoadPageFault(_) => Some(13),
+            Exception::StoreAMOPageFault(_) => Some(15),
+            Exception::StackSizeExceeded
+            | Exception::InvalidAddr
+            | Exception::LoadFromBuffer
+            | Exception::OutOfGas(_)
+            | Exception::InvalidElf(_)
+            | Exception::State(_)
+            | Exception::General => None,
+        }
+    }
+}
+
 impl Display for Exception {
     fn fmt(&self, f: &mut Formatter) -> Result {
         write!(f, "{:?}", self)
@@ -1,3 +1,8 @@
+use std::collections::BTreeSet;
+use std::error::Error;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum Base {
     I32,
@@ -12,6 +17,8 @@ pub enum Extension {
     F,
     D,
     G,
+    // G with the compressed instructions, i.e. GC.
+    C,
 }
 
 impl From<Base> for &'static str {
@@ -41,6 +48,7 @@ impl From<Extension> for &'static str {
             Extension::F => return "F",
             Extension::D => return "D",
             Extension::G => return "G",
+            Extension::C => return "C",
         }
     }
 }
@@ -53,7 +61,8 @@ impl From<&Extension> for &'static str {
             Extension::A => return "A",
             Extension::F => return "F",
             Extension::D => return "D",
-            Extension::G => return "G"
+            Extension::G => return "G",
+            Extension::C => return "C"
         }
     }
 }
@@ -77,6 +86,7 @@ impl From<&'static str> for Extension {
             "F" => return Extension::F,
             "D" => return Extension::D,
             "G" => return Extension::G,
+            "C" => return Extension::C,
             _ => return Extension::I
         }
     }
@@ -93,3 +103,197 @@ impl Base {
         self.into()
     }
 }
+
+/// A set of ISA extensions, e.g. parsed from "rv64imac_zicsr_zifencei".
+/// Single letter extensions are kept as their `misa` bits, multi-letter
+/// ones (Zicsr, Zifencei, ...) by lowercase name.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ExtensionSet {
+    letters: u32,
+    named: BTreeSet<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum IsaError {
+    // The string has no "rv32"/"rv64" prefix where one is required.
+    MissingXlen,
+    // The xlen of the "rv" prefix is neither 32 nor 64.
+    UnsupportedXlen(String),
+    // Neither the I nor the E base integer ISA is in the set.
+    MissingBase,
+    InvalidExtension(char),
+    // The first extension requires the second one, e.g. D requires F.
+    MissingDependency(char, char),
+}
+
+impl Display for IsaError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for IsaError {}
+
+// G is shorthand for IMAFD_Zicsr_Zifencei.
+const G_LETTERS: &str = "imafd";
+const G_NAMED: [&str; 2] = ["zicsr", "zifencei"];
+
+impl ExtensionSet {
+    pub fn new() -> ExtensionSet {
+        ExtensionSet::default()
+    }
+
+    // Adds a single letter extension ("m") or a named one ("zicsr"), in
+    // any case. "g" adds everything G stands for.
+    pub fn insert(&mut self, ext: &str) {
+        let ext = ext.to_ascii_lowercase();
+        match ext.as_bytes() {
+            [b'g'] => {
+                G_LETTERS.chars().for_each(|c| self.insert_letter(c));
+                G_NAMED.iter().for_each(|name| self.insert(name));
+            },
+            [c] if c.is_ascii_lowercase() => self.insert_letter(char::from(*c)),
+            _ => {
+                self.named.insert(ext);
+            },
+        }
+    }
+
+    fn insert_letter(&mut self, c: char) {
+        self.letters |= 1 << (c as u32 - 'a' as u32);
+    }
+
+    pub fn with(mut self, ext: &str) -> ExtensionSet {
+        self.insert(ext);
+        self
+    }
+
+    // Whether an extension, as named by the `Ext` property of an
+    // instruction, is in the set.
+    pub fn contains(&self, ext: &str) -> bool {
+        let ext = ext.to_ascii_lowercase();
+        match ext.as_bytes() {
+            [c] if c.is_ascii_lowercase() => self.letters & (1 << (c - b'a')) != 0,
+            _ => self.named.contains(&ext),
+        }
+    }
+
+    // The extension bits of misa, bit 0 is A and bit 25 is Z.
+    pub fn letters(&self) -> u32 {
+        self.letters
+    }
+
+    // The value of the misa CSR for a hart with this set on `base`.
+    pub fn misa(&self, base: Base) -> u64 {
+        let mxl = match base {
+            Base::I32 => 1u64 << 30,
+            Base::I64 => 2u64 << 62,
+        };
+        mxl | u64::from(self.letters)
+    }
+
+    fn validate(&self) -> Result<(), IsaError> {
+        if !self.contains("i") && !self.contains("e") {
+            return Err(IsaError::MissingBase);
+        }
+        for (ext, dep) in [('d', 'f'), ('q', 'd')] {
+            if self.contains(&ext.to_string()) && !self.contains(&dep.to_string()) {
+                return Err(IsaError::MissingDependency(ext, dep));
+            }
+        }
+        Ok(())
+    }
+}
+
+// Parses an ISA string with its "rv32"/"rv64" prefix.
+pub fn parse_isa(isa: &str) -> Result<(Base, ExtensionSet), IsaError> {
+    let isa = isa.trim().to_ascii_lowercase();
+    let (base, exts) = split_xlen(&isa)?;
+    Ok((base.ok_or(IsaError::MissingXlen)?, exts.parse()?))
+}
+
+// Splits off an optional "rv32"/"rv64" prefix.
+fn split_xlen(isa: &str) -> Result<(Option<Base>, &str), IsaError> {
+    let Some(rest) = isa.strip_prefix("rv") else {
+        return Ok((None, isa));
+    };
+    let digits = rest.bytes().take_while(u8::is_ascii_digit).count();
+    match &rest[..digits] {
+        "32" => Ok((Some(Base::I32), &rest[digits..])),
+        "64" => Ok((Some(Base::I64), &rest[digits..])),
+        xlen => Err(IsaError::UnsupportedXlen(xlen.to_string())),
+    }
+}
+
+// The length of a version number such as "2p0" at the start of `s`.
+fn version_len(s: &str) -> usize {
+    let digits = |s: &str| s.bytes().take_while(u8::is_ascii_digit).count();
+    let major = digits(s);
+    if major == 0 {
+        return 0;
+    }
+    match s[major..].strip_prefix('p').map(digits) {
+        Some(minor) if minor > 0 => major + 1 + minor,
+        _ => major,
+    }
+}
+
+// Drops a trailing version number such as "2p0" from an extension name.
+fn strip_version(name: &str) -> &str {
+    let end = (1..name.len())
+        .find(|&i| name.as_bytes()[i].is_ascii_digit() && version_len(&name[i..]) == name.len() - i)
+        .unwrap_or(name.len());
+    &name[..end]
+}
+
+impl FromStr for ExtensionSet {
+    type Err = IsaError;
+
+    // Accepts an ISA string with or without its "rv32"/"rv64" prefix.
+    // Single letter extensions come first, multi-letter ones (starting
+    // with z, s or x) are separated by underscores. Version numbers are
+    // accepted and ignored.
+    fn from_str(isa: &str) -> Result<ExtensionSet, IsaError> {
+        let isa = isa.trim().to_ascii_lowercase();
+        let (_, exts) = split_xlen(&isa)?;
+        let mut set = ExtensionSet::new();
+        for part in exts.split('_').filter(|part| !part.is_empty()) {
+            let mut i = 0;
+            while i < part.len() {
+                let c = char::from(part.as_bytes()[i]);
+                match c {
+                    'z' | 's' | 'x' => {
+                        set.insert(strip_version(&part[i..]));
+                        break;
+                    },
+                    'a'..='y' => set.insert(&c.to_string()),
+                    _ => return Err(IsaError::InvalidExtension(c)),
+                }
+                i += 1 + version_len(&part[i + 1..]);
+            }
+        }
+        set.validate()?;
+
+        Ok(set)
+    }
+}
+
+// The sets the single value extensions stood for. Each includes the
+// extensions it was built on, and the CSR and fence.i instructions.
+impl From<Extension> for ExtensionSet {
+    fn from(ext: Extension) -> ExtensionSet {
+        let letters = match ext {
+            Extension::I => "i",
+            Extension::M => "im",
+            Extension::A => "ia",
+            Extension::F => "if",
+            Extension::D => "ifd",
+            Extension::G => G_LETTERS,
+            Extension::C => "imafdc",
+        };
+        let mut set = ExtensionSet::new();
+        letters.chars().for_each(|c| set.insert_letter(c));
+        G_NAMED.iter().for_each(|name| set.insert(name));
+        set
+    }
+}
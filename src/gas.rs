@@ -0,0 +1,77 @@
+#![allow(unused, unused_mut, dead_code)]
+use crate::instructions::Instruction;
+use std::collections::HashMap;
+use strum::EnumProperty;
+
+pub const DEFAULT_COST: u64 = 1;
+pub const MULTIPLY_COST: u64 = 4;
+pub const ATOMIC_COST: u64 = 4;
+pub const FLOAT_COST: u64 = 2;
+pub const SYSCALL_COST: u64 = 40;
+pub const MEMORY_PAGE_COST: u64 = 256;
+// The gas a hart may spend before it runs out, unless set otherwise.
+pub const DEFAULT_GAS_LIMIT: u64 = 1_000_000;
+
+/// The gas charged while executing a program. An instruction costs what is
+/// set for its variant, else what is set for its extension (the `Ext`
+/// property of the `Instruction` enum), else `default`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CostSchedule {
+    pub default: u64,
+    // Charged on top of the instruction for every ecall.
+    pub syscall: u64,
+    // Charged the first time the program stores to a page.
+    pub memory_page: u64,
+    ext: HashMap<&'static str, u64>,
+    instructions: HashMap<&'static str, u64>,
+}
+
+impl CostSchedule {
+    // A schedule where every instruction costs `default` and nothing else
+    // is charged.
+    pub fn flat(default: u64) -> CostSchedule {
+        CostSchedule {
+            default,
+            syscall: 0,
+            memory_page: 0,
+            ext: HashMap::new(),
+            instructions: HashMap::new(),
+        }
+    }
+
+    // Sets the cost of every instruction of an extension, e.g. "M".
+    pub fn set_ext_cost(&mut self, ext: &'static str, cost: u64) {
+        self.ext.insert(ext, cost);
+    }
+
+    // Sets the cost of an instruction variant by name, e.g. "Div".
+    pub fn set_cost(&mut self, variant: &'static str, cost: u64) {
+        self.instructions.insert(variant, cost);
+    }
+
+    pub fn cost(&self, inst: &Instruction) -> u64 {
+        let variant: &'static str = inst.into();
+        if let Some(cost) = self.instructions.get(variant) {
+            return *cost;
+        }
+        inst.get_str("Ext")
+            .and_then(|ext| self.ext.get(ext))
+            .copied()
+            .unwrap_or(self.default)
+    }
+}
+
+impl Default for CostSchedule {
+    fn default() -> CostSchedule {
+        let mut costs = CostSchedule::flat(DEFAULT_COST);
+        costs.syscall = SYSCALL_COST;
+        costs.memory_page = MEMORY_PAGE_COST;
+        costs.set_ext_cost("M", MULTIPLY_COST);
+        costs.set_ext_cost("A", ATOMIC_COST);
+        costs.set_ext_cost("F", FLOAT_COST);
+        costs.set_ext_cost("D", FLOAT_COST);
+        costs.set_ext_cost("Q", FLOAT_COST);
+
+        costs
+    }
+}
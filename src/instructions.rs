@@ -16,7 +16,7 @@ pub const SEVEN_BIT_MASK: u32 = 0b1111111 as u32;
 // When we implement decoding, based on the Extension set of the type of machine
 // We will know which OpCodes are Invalid, because they will return an Invalid
 // variant of the OpCodeType.
-#[derive(Clone, Copy, Debug, PartialEq, strum_macros::EnumIter, strum_macros::EnumProperty)]
+#[derive(Clone, Copy, Debug, PartialEq, strum_macros::EnumIter, strum_macros::EnumProperty, strum_macros::IntoStaticStr)]
 pub enum Instruction {
     #[strum(props(Base = "None", Ext = "None"))]
     Undefined,
@@ -304,6 +304,15 @@ pub enum Instruction {
     ECall,
     #[strum(props(Base = "32", Ext = "I"))]
     EBreak,
+    #[strum(props(Base = "32", Ext = "I"))]
+    Sret,
+    #[strum(props(Base = "32", Ext = "I"))]
+    Mret,
+    #[strum(props(Base = "32", Ext = "I"))]
+    SfenceVma {
+        rs1: Register,
+        rs2: Register,
+    },
     #[strum(props(Base = "64", Ext = "I"))]
     Lwu {
         rd: Register,
@@ -396,49 +405,49 @@ pub enum Instruction {
         func3: u32,
         func7: u32,
     },
-    #[strum(props(Base = "32", Ext = "I"))]
+    #[strum(props(Base = "32", Ext = "Zifencei"))]
     FenceI {
         rd: Register,
         rs1: Register,
         imm: i32,
         func3: u32,
     },
-    #[strum(props(Base = "32", Ext = "I"))]
+    #[strum(props(Base = "32", Ext = "Zicsr"))]
     Csrrw {
         rd: Register,
         rs1: Register,
         csr: i32,
         func3: u32,
     },
-    #[strum(props(Base = "32", Ext = "I"))]
+    #[strum(props(Base = "32", Ext = "Zicsr"))]
     Csrrs {
         rd: Register,
         rs1: Register,
         csr: i32,
         func3: u32,
     },
-    #[strum(props(Base = "32", Ext = "I"))]
+    #[strum(props(Base = "32", Ext = "Zicsr"))]
     Csrrc {
         rd: Register,
         rs1: Register,
         csr: i32,
         func3: u32,
     },
-    #[strum(props(Base = "32", Ext = "I"))]
+    #[strum(props(Base = "32", Ext = "Zicsr"))]
     Csrrwi {
         rd: Register,
         uimm: u32,
         csr: i32,
         func3: u32,
     },
-    #[strum(props(Base = "32", Ext = "I"))]
+    #[strum(props(Base = "32", Ext = "Zicsr"))]
     Csrrsi {
         rd: Register,
         uimm: u32,
         csr: i32,
         func3: u32,
     },
-    #[strum(props(Base = "32", Ext = "I"))]
+    #[strum(props(Base = "32", Ext = "Zicsr"))]
     Csrrci {
         rd: Register,
         uimm: u32,
@@ -1833,18 +1842,25 @@ impl From<Inst> for Instruction {
                 match func3 {
                     0b000 => {
                         match imm {
-                            0b000000000000 => {
-                                assert!(func3 == 0b000);
-                                assert!(unpacked.rs1.unwrap() == 0b00000);
-                                assert!(unpacked.rd.unwrap() == 0b00000);
+                            0b000000000000 if unpacked.rs1 == Some(0) && unpacked.rd == Some(0) => {
                                 return Instruction::ECall;
                             }
-                            0b000000000001 => {
-                                assert!(func3 == 0b000);
-                                assert!(unpacked.rs1.unwrap() == 0b00000);
-                                assert!(unpacked.rd.unwrap() == 0b00000);
+                            0b000000000001 if unpacked.rs1 == Some(0) && unpacked.rd == Some(0) => {
                                 return Instruction::EBreak;
                             }
+                            0b000100000010 if unpacked.rs1 == Some(0) && unpacked.rd == Some(0) => {
+                                return Instruction::Sret;
+                            }
+                            0b001100000010 if unpacked.rs1 == Some(0) && unpacked.rd == Some(0) => {
+                                return Instruction::Mret;
+                            }
+                            // sfence.vma keeps rs2 in the low bits of the immediate.
+                            _ if imm >> 5 == 0b0001001 && unpacked.rd == Some(0) => {
+                                return Instruction::SfenceVma {
+                                    rs1: unpacked.rs1.unwrap().into(),
+                                    rs2: ((imm & 0b11111) as usize).into(),
+                                };
+                            }
                             _ => return Instruction::Undefined,
                         }
                     },
@@ -3288,102 +3304,10 @@ impl InstructionDecoder for Instruction {
         }
 
         let instruction: Instruction = inst.into();
-        let instruction_base: Base = instruction.get_str("Base").unwrap().into();
-        let instruction_ext: Extension = instruction.get_str("Ext").unwrap().into();
-
-        match enc_table.get_base() {
-            Base::I32 => {
-                match instruction_base {
-                    Base::I32 => {
-                        match enc_table.get_ext() {
-                            Extension::I => {
-                                match instruction_ext {
-                                    Extension::I => return instruction,
-                                    _ => return Instruction::Undefined
-                                }
-                            }
-                            Extension::M => {
-                                match instruction_ext {
-                                    Extension::I => return instruction,
-                                    Extension::M => return instruction,
-                                    _ => return Instruction::Undefined
-                                }
-                            }
-                            Extension::A => {
-                                match instruction_ext {
-                                    Extension::I => return instruction,
-                                    Extension::A => return instruction,
-                                    _ => return Instruction::Undefined
-                                }
-                            }
-                            Extension::F => {
-                                match instruction_ext {
-                                    Extension::I => return instruction,
-                                    Extension::F => return instruction,
-                                    _ => return Instruction::Undefined
-                                }
-                            }
-                            Extension::D => {
-                                match instruction_ext {
-                                    Extension::I => return instruction,
-                                    Extension::F => return instruction,
-                                    Extension::D => return instruction,
-                                    _ => return Instruction::Undefined
-                                }
-                            }
-                            Extension::G => {
-                                return instruction
-                            }
-                        }
-                    }
-                    Base::I64 => {
-                        return Instruction::Undefined
-                    }
-                }
-
-            }
-            Base::I64 => {
-                match enc_table.get_ext() {
-                    Extension::I => {
-                        match instruction_ext {
-                            Extension::I => return instruction,
-                            _ => return Instruction::Undefined
-                        }
-                    }
-                    Extension::M => {
-                        match instruction_ext {
-                            Extension::I => return instruction,
-                            Extension::M => return instruction,
-                            _ => return Instruction::Undefined
-                        }
-                    }
-                    Extension::A => {
-                        match instruction_ext {
-                            Extension::I => return instruction,
-                            Extension::A => return instruction,
-                            _ => return Instruction::Undefined
-                        }
-                    }
-                    Extension::F => {
-                        match instruction_ext {
-                            Extension::I => return instruction,
-                            Extension::F => return instruction,
-                            _ => return Instruction::Undefined
-                        }
-                    }
-                    Extension::D => {
-                        match instruction_ext {
-                            Extension::I => return instruction,
-                            Extension::F => return instruction,
-                            Extension::D => return instruction,
-                            _ => return Instruction::Undefined
-                        }
-                    }
-                    Extension::G => {
-                        return instruction
-                    }
-                }
-            }
+        if enc_table.supports(&instruction) {
+            instruction
+        } else {
+            Instruction::Undefined
         }
     }
 }
@@ -5,14 +5,14 @@ pub trait Machine {
     type Error;
 
     fn pc(&mut self) -> &Self::Reg;
-    fn update_pc(&self, pc: Self::Reg);
+    fn update_pc(&mut self, pc: Self::Reg);
     fn commit_pc(&mut self);
     fn memory(&self) -> &Self::Mem;
     fn memory_mut(&mut self) -> &mut Self::Mem;
     fn registers(&self) -> &[Self::Reg];
     fn set_register(&mut self, idx: usize, value: Self::Reg);
     fn base(&self) -> u8;
-    fn ext(&self) -> u8;
+    fn ext(&self) -> u32;
     fn version(&self) -> u32;
     fn ecall(&mut self) -> Result<(), Self::Error>;
     fn ebreak(&mut self) -> Result<(), Self::Error>;
@@ -24,6 +24,7 @@ pub trait Support: Machine {
     fn cycles(&self) -> u64;
     fn set_cycles(&mut self, cycles: u64);
     fn max_cycles(&self) -> u64;
+    fn set_max_cycles(&mut self, max_cycles: u64);
     fn running(&self) -> bool;
     fn set_running(&mut self, running: bool);
     fn reset(&mut self, max_cycles: u64);
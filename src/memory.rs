@@ -1,22 +1,28 @@
 #![allow(unused, unused_mut, dead_code)]
 use crate::exceptions::Exception;
+use crate::csr::CsrFile;
 use crate::register::RegisterValue;
 use std::fmt::{Display, Formatter};
 use std::error::Error;
-use crate::consts::{MAX_MEM, INDICES, INDEX_SHIFTS, DIRTY};
+use crate::consts::{
+    MAX_MEM, INDEX_SIZE, INDEX_SHIFTS, FRAME_INDEX_SHIFTS, STACK_SIZE, HEAP_SIZE, DIRTY, EXECUTABLE, FROZEN,
+};
+use std::collections::{HashMap, HashSet};
+use std::ops::Range;
+use std::sync::Arc;
 
 pub const BASE: u64 = 0x8000_0000;
 pub const BYTE: u8 = 8;
 pub const DOUBLEWORD: u8 = 64;
 pub const HALFWORD: u8 = 16;
 pub const WORD: u8 = 32;
-pub const MEM_SIZE: u64 = 1024 * 1024 * 128;
 
 // Trait to provide memory functionality and types. 
 // b == Byte
 // hw == Half Word
 // w == Word
 // dw == DoubleWord
+// Every access is bounds checked, addresses outside the memory are errors.
 pub trait Memory: Default {
     type RegValue: RegisterValue + From<u8> + From<u16> + From<u32> + From<u64>;
     type Bytes;
@@ -26,23 +32,41 @@ pub trait Memory: Default {
     fn get_flag(&mut self, index: u64) -> Result<u8, Self::Error>;
     fn set_flag(&mut self, index: u64, flag: u8) -> Result<(), Self::Error>;
     fn clear_flag(&mut self, index: u64, flag: u8) -> Result<(), Self::Error>;
-    fn get_indices(addr: u64, size: u64) -> Result<(u64, u64), Self::Error>;
+    // The first and last page index of [addr, addr + size).
+    fn get_indices(&self, addr: u64, size: u64) -> Result<(u64, u64), Self::Error>;
+    // Checks [addr, addr + size) can be written: FROZEN pages are immutable
+    // and EXECUTABLE pages are never writable (W^X).
+    fn check_write(&self, addr: u64, size: u64) -> Result<(), Self::Error>;
 
-    fn execute_readhw(&mut self, addr: u64) -> Self::RegValue;
-    fn execute_readw(&mut self, addr: u64) -> Self::RegValue;
+    // Instruction fetches, only from EXECUTABLE pages.
+    fn execute_readhw(&mut self, addr: u64) -> Result<Self::RegValue, Self::Error>;
+    fn execute_readw(&mut self, addr: u64) -> Result<Self::RegValue, Self::Error>;
 
     fn read(&self, addr: &Self::RegValue, size: u8) -> Result<Self::RegValue, Self::Error>;
-    fn readb(&self, addr: &Self::RegValue) -> Self::RegValue;
-    fn readhw(&self, addr: &Self::RegValue) -> Self::RegValue;
-    fn readw(&self, addr: &Self::RegValue) -> Self::RegValue;
-    fn readdw(&self, addr: &Self::RegValue) -> Self::RegValue;
+    fn readb(&self, addr: &Self::RegValue) -> Result<Self::RegValue, Self::Error>;
+    fn readhw(&self, addr: &Self::RegValue) -> Result<Self::RegValue, Self::Error>;
+    fn readw(&self, addr: &Self::RegValue) -> Result<Self::RegValue, Self::Error>;
+    fn readdw(&self, addr: &Self::RegValue) -> Result<Self::RegValue, Self::Error>;
+    // A read made by the hart. Unlike `read` it may change the state of a
+    // device, as reading a UART register does.
+    fn load(&mut self, addr: &Self::RegValue, size: u8) -> Result<Self::RegValue, Self::Error> {
+        self.read(addr, size)
+    }
+    // Advances the devices behind the memory by one tick, they raise their
+    // interrupts in the mip of `csr`. Plain memory has no devices.
+    fn increment(&mut self, csr: &mut CsrFile) {}
+    // Whether a device keeps the time CSR of the hart, see `CsrFile::tick`.
+    fn keeps_time(&self) -> bool {
+        false
+    }
 
+    fn read_array(&self, addr: Self::RegValue, size: u64) -> Result<Self::Bytes, Self::Error>;
     fn write_array(&mut self, addr: Self::RegValue, val: Self::Bytes) -> Result<(), Self::Error>;
     fn write(&mut self, addr: u64, value: u64, size: u8) -> Result<(), Self::Error>;
-    fn writeb(&mut self, addr: Self::RegValue, val: Self::RegValue);
-    fn writehw(&mut self, addr: Self::RegValue, val: Self::RegValue);
-    fn writew(&mut self, addr: Self::RegValue, val: Self::RegValue);
-    fn writedw(&mut self, addr: u64, val: u64);
+    fn writeb(&mut self, addr: Self::RegValue, val: Self::RegValue) -> Result<(), Self::Error>;
+    fn writehw(&mut self, addr: Self::RegValue, val: Self::RegValue) -> Result<(), Self::Error>;
+    fn writew(&mut self, addr: Self::RegValue, val: Self::RegValue) -> Result<(), Self::Error>;
+    fn writedw(&mut self, addr: u64, val: u64) -> Result<(), Self::Error>;
     fn into_u64(&self, val: &Self::RegValue) -> u64;
     fn into_i64(&self, val: &Self::RegValue) -> i64;
     fn into_u32(&self, val: &Self::RegValue) -> u32;
@@ -51,35 +75,447 @@ pub trait Memory: Default {
 
 }
 
-pub trait ReadOnlyMemory: Default {}
+// Memory the hart can read and execute but not write, like a boot ROM.
+// Addresses are offsets from the start of the memory.
+pub trait ReadOnlyMemory: Default {
+    fn size(&self) -> u64;
+    fn read(&self, offset: u64, size: u8) -> Result<u64, MemError>;
+}
+
+/// A read only image, bytes past its end read as zeros up to `size`.
+#[derive(Debug, Clone, Default)]
+pub struct Rom {
+    data: Vec<u8>,
+    size: u64,
+}
+
+impl Rom {
+    pub fn new(data: Vec<u8>, size: u64) -> Rom {
+        let size = std::cmp::max(size, data.len() as u64);
+        Rom { data, size }
+    }
+}
+
+impl ReadOnlyMemory for Rom {
+    fn size(&self) -> u64 {
+        self.size
+    }
+
+    fn read(&self, offset: u64, size: u8) -> Result<u64, MemError> {
+        let len = u64::from(size / 8);
+        if !matches!(size, BYTE | HALFWORD | WORD | DOUBLEWORD) || offset.checked_add(len).is_none_or(|end| end > self.size) {
+            return Err(MemError::OutOfBounds);
+        }
+        let mut bytes = [0; 8];
+        for (i, byte) in bytes.iter_mut().take(len as usize).enumerate() {
+            *byte = self.data.get(offset as usize + i).copied().unwrap_or(0);
+        }
+        Ok(u64::from_le_bytes(bytes))
+    }
+}
+
+/// Where the memory of a hart lives and how it is split. Code is loaded at
+/// `base`, the heap follows the code and the stack sits at the top of the
+/// memory, growing down towards the heap.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MemoryLayout {
+    pub base: u64,
+    pub size: u64,
+    pub stack_size: u64,
+    pub heap_size: u64,
+}
+
+impl MemoryLayout {
+    // The base and every region must be page aligned, and the stack and
+    // heap must fit in the memory.
+    pub fn new(base: u64, size: u64, stack_size: u64, heap_size: u64) -> Result<MemoryLayout, MemError> {
+        let page = INDEX_SIZE as u64;
+        let aligned = [base, size, stack_size, heap_size].iter().all(|val| val.is_multiple_of(page));
+        let fits = stack_size.checked_add(heap_size).is_some_and(|used| used <= size);
+        if size == 0 || !aligned || !fits || base.checked_add(size).is_none() {
+            return Err(MemError::InvalidLayout);
+        }
+        Ok(MemoryLayout { base, size, stack_size, heap_size })
+    }
+
+    pub fn end(&self) -> u64 {
+        self.base + self.size
+    }
+
+    pub fn stack(&self) -> Range<u64> {
+        self.end() - self.stack_size..self.end()
+    }
+
+    pub fn heap(&self) -> Range<u64> {
+        let start = self.stack().start - self.heap_size;
+        start..start + self.heap_size
+    }
+
+    // The room left for code below the heap.
+    pub fn code_size(&self) -> u64 {
+        self.size - self.stack_size - self.heap_size
+    }
+
+    pub fn pages(&self) -> usize {
+        (self.size >> INDEX_SHIFTS) as usize
+    }
+}
+
+// 4 MiB at BASE, where the bus maps DRAM, of which the top 1 MiB is stack
+// and the 1 MiB below it heap.
+impl Default for MemoryLayout {
+    fn default() -> MemoryLayout {
+        MemoryLayout {
+            base: BASE,
+            size: MAX_MEM as u64,
+            stack_size: STACK_SIZE as u64,
+            heap_size: HEAP_SIZE as u64,
+        }
+    }
+}
+
+// A page of memory, allocated on its first write. Pages are shared with
+// snapshots and copied when written while shared.
+type Page = Arc<[u8; INDEX_SIZE]>;
 
+// FRAME_SIZE worth of pages and their flags. A frame is allocated when one
+// of its pages is written or flagged.
 #[derive(Debug, Clone)]
-pub struct Dram {
-    pub mem: Vec<u8>,
+struct Frame {
+    pages: Vec<Option<Page>>,
     flags: Vec<u8>,
+}
+
+impl Frame {
+    fn new() -> Frame {
+        Frame {
+            pages: vec![None; PAGES_PER_FRAME],
+            flags: vec![0; PAGES_PER_FRAME],
+        }
+    }
+}
+
+const PAGES_PER_FRAME: usize = 1 << FRAME_INDEX_SHIFTS;
+const PAGE_MASK: u64 = INDEX_SIZE as u64 - 1;
+
+// The pages and flags modified since a snapshot was taken, as they were
+// before their first modification.
+#[derive(Debug, Clone, Default)]
+struct Journal {
+    // The id of the snapshot that opened the journal.
+    id: u64,
+    saved: HashMap<usize, (Option<Page>, u8)>,
+}
+
+/// A point `Dram` can be rolled back to, see `Dram::snapshot`. It holds the
+/// position of its journal and an id that is never handed out twice, so a
+/// snapshot that was restored or committed stays invalid once its
+/// position is reused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Snapshot(usize, u64);
+
+/// Main memory of a hart. Guest addresses are offsets from the base of its
+/// layout, which defaults to `BASE` where RISC-V boards and the bus put
+/// DRAM.
+///
+/// Memory is sparse: pages are allocated on their first write, grouped in
+/// frames, and pages that were never written read as zeros.
+///
+/// Snapshots are copy-on-write: taking one is free, and the first write to
+/// a page after it keeps the old page aside, so restoring or committing a
+/// snapshot only costs the pages written since.
+#[derive(Debug, Clone)]
+pub struct Dram {
+    frames: Vec<Option<Box<Frame>>>,
+    resident: usize,
+    journals: Vec<Journal>,
+    // The id of the next snapshot.
+    next_snapshot: u64,
+    // Pages whose contents changed since the last `take_modified`.
+    modified: HashSet<usize>,
     size: u64,
+    layout: MemoryLayout,
 }
 
 impl Dram {
-    pub fn new() -> Dram {
+    pub fn new(layout: MemoryLayout) -> Dram {
         Dram {
-            mem: vec![0; MAX_MEM],
-            flags: vec![0; INDICES],
-            size: 0
+            frames: vec![None; layout.pages().div_ceil(PAGES_PER_FRAME)],
+            resident: 0,
+            journals: vec![],
+            next_snapshot: 0,
+            modified: HashSet::new(),
+            size: 0,
+            layout,
         }
     }
 
-    pub fn init(&mut self, bin: Vec<u8>) {
+    pub fn init(&mut self, bin: Vec<u8>) -> Result<(), MemError> {
+        let base = self.layout.base;
+        self.write_bytes(base, &bin)?;
         self.size = bin.len() as u64;
-        self.mem.splice(..bin.len(), bin.iter().cloned());
+        Ok(())
+    }
+
+    pub fn base(&self) -> u64 {
+        self.layout.base
+    }
+
+    pub fn layout(&self) -> &MemoryLayout {
+        &self.layout
+    }
+
+    // The number of pages backed by host memory.
+    pub fn resident_pages(&self) -> usize {
+        self.resident
+    }
+
+    // The offset of [addr, addr + size) from the start of the memory.
+    fn offset(&self, addr: u64, size: u64) -> Result<u64, MemError> {
+        let offset = addr.checked_sub(self.layout.base).ok_or(MemError::OutOfBounds)?;
+        match offset.checked_add(size) {
+            Some(end) if end <= self.layout.size => Ok(offset),
+            _ => Err(MemError::OutOfBounds),
+        }
+    }
+
+    /// The contents of page `idx`, None when it was never written and
+    /// reads as zeros.
+    pub fn page_data(&self, idx: usize) -> Option<&[u8; INDEX_SIZE]> {
+        self.frames.get(idx >> FRAME_INDEX_SHIFTS)?.as_ref()?.pages[idx & (PAGES_PER_FRAME - 1)].as_deref()
+    }
+
+    // The pages backed by host memory.
+    pub(crate) fn resident(&self) -> impl Iterator<Item = usize> + '_ {
+        self.frames.iter().enumerate().flat_map(|(i, frame)| {
+            frame.iter().flat_map(move |frame| {
+                frame.pages.iter().enumerate()
+                    .filter(|(_, page)| page.is_some())
+                    .map(move |(j, _)| (i << FRAME_INDEX_SHIFTS) | j)
+            })
+        })
+    }
+
+    // Returns the pages whose contents changed since the last call.
+    pub(crate) fn take_modified(&mut self) -> HashSet<usize> {
+        std::mem::take(&mut self.modified)
+    }
+
+    fn page(&self, idx: usize) -> Option<&Page> {
+        self.frames[idx >> FRAME_INDEX_SHIFTS].as_ref()?.pages[idx & (PAGES_PER_FRAME - 1)].as_ref()
+    }
+
+    /// Takes a snapshot of memory, page contents and flags alike.
+    /// Snapshots nest: restoring or committing one does the same to the
+    /// snapshots taken after it.
+    pub fn snapshot(&mut self) -> Snapshot {
+        let id = self.next_snapshot;
+        self.next_snapshot += 1;
+        self.journals.push(Journal { id, saved: HashMap::new() });
+        Snapshot(self.journals.len() - 1, id)
+    }
+
+    // Checks `snapshot` is still open.
+    fn check_snapshot(&self, snapshot: Snapshot) -> Result<(), MemError> {
+        match self.journals.get(snapshot.0) {
+            Some(journal) if journal.id == snapshot.1 => Ok(()),
+            _ => Err(MemError::InvalidSnapshot),
+        }
+    }
+
+    /// Rolls memory back to `snapshot`, undoing every write since,
+    /// including the DIRTY flags they set.
+    pub fn restore(&mut self, snapshot: Snapshot) -> Result<(), MemError> {
+        self.check_snapshot(snapshot)?;
+        while self.journals.len() > snapshot.0 {
+            let journal = self.journals.pop().unwrap_or_default();
+            for (idx, (page, flags)) in journal.saved {
+                let frame = self.frames[idx >> FRAME_INDEX_SHIFTS].get_or_insert_with(|| Box::new(Frame::new()));
+                let slot = &mut frame.pages[idx & (PAGES_PER_FRAME - 1)];
+                match (slot.is_some(), page.is_some()) {
+                    (false, true) => self.resident += 1,
+                    (true, false) => self.resident -= 1,
+                    _ => {},
+                }
+                *slot = page;
+                self.modified.insert(idx);
+                frame.flags[idx & (PAGES_PER_FRAME - 1)] = flags;
+            }
+        }
+        Ok(())
+    }
+
+    /// Keeps the writes made since `snapshot` and forgets it. They can
+    /// still be undone by restoring an earlier snapshot.
+    pub fn commit(&mut self, snapshot: Snapshot) -> Result<(), MemError> {
+        self.check_snapshot(snapshot)?;
+        while self.journals.len() > snapshot.0 {
+            let journal = self.journals.pop().unwrap_or_default();
+            if let Some(parent) = self.journals.last_mut() {
+                for (idx, saved) in journal.saved {
+                    parent.saved.entry(idx).or_insert(saved);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    // Records a page in the latest journal before it is first modified.
+    fn save(&mut self, idx: usize) {
+        let saved = match self.journals.last() {
+            Some(journal) if !journal.saved.contains_key(&idx) => (self.page(idx).cloned(), self.flag(idx as u64)),
+            _ => return,
+        };
+        if let Some(journal) = self.journals.last_mut() {
+            journal.saved.insert(idx, saved);
+        }
+    }
+
+    fn page_mut(&mut self, idx: usize) -> &mut [u8; INDEX_SIZE] {
+        self.save(idx);
+        self.modified.insert(idx);
+        let frame = self.frames[idx >> FRAME_INDEX_SHIFTS].get_or_insert_with(|| Box::new(Frame::new()));
+        let page = &mut frame.pages[idx & (PAGES_PER_FRAME - 1)];
+        if page.is_none() {
+            self.resident += 1;
+        }
+        Arc::make_mut(page.get_or_insert_with(|| Arc::new([0; INDEX_SIZE])))
+    }
+
+    // Releases a page, it reads as zeros again.
+    fn drop_page(&mut self, idx: usize) {
+        self.save(idx);
+        if let Some(frame) = &mut self.frames[idx >> FRAME_INDEX_SHIFTS] {
+            if frame.pages[idx & (PAGES_PER_FRAME - 1)].take().is_some() {
+                self.modified.insert(idx);
+                self.resident -= 1;
+            }
+        }
+    }
+
+    fn flag(&self, idx: u64) -> u8 {
+        let idx = idx as usize;
+        self.frames[idx >> FRAME_INDEX_SHIFTS]
+            .as_ref()
+            .map_or(0, |frame| frame.flags[idx & (PAGES_PER_FRAME - 1)])
+    }
+
+    fn flag_mut(&mut self, idx: u64) -> &mut u8 {
+        let idx = idx as usize;
+        self.save(idx);
+        let frame = self.frames[idx >> FRAME_INDEX_SHIFTS].get_or_insert_with(|| Box::new(Frame::new()));
+        &mut frame.flags[idx & (PAGES_PER_FRAME - 1)]
+    }
+
+    // Splits [offset, offset + size) at page boundaries into
+    // (page, offset in page, length) pieces.
+    fn pieces(offset: u64, size: u64) -> impl Iterator<Item = (usize, usize, usize)> {
+        let end = offset + size;
+        let mut cursor = offset;
+        std::iter::from_fn(move || {
+            if cursor >= end {
+                return None;
+            }
+            let len = std::cmp::min(end - cursor, INDEX_SIZE as u64 - (cursor & PAGE_MASK));
+            let piece = ((cursor >> INDEX_SHIFTS) as usize, (cursor & PAGE_MASK) as usize, len as usize);
+            cursor += len;
+            Some(piece)
+        })
+    }
+
+    fn read_bytes(&self, addr: u64, buf: &mut [u8]) -> Result<(), MemError> {
+        let offset = self.offset(addr, buf.len() as u64)?;
+        let mut done = 0;
+        for (idx, start, len) in Dram::pieces(offset, buf.len() as u64) {
+            match self.page(idx) {
+                Some(page) => buf[done..done + len].copy_from_slice(&page[start..start + len]),
+                None => memset(&mut buf[done..done + len], 0),
+            }
+            done += len;
+        }
+        Ok(())
+    }
+
+    fn write_bytes(&mut self, addr: u64, bytes: &[u8]) -> Result<(), MemError> {
+        let offset = self.offset(addr, bytes.len() as u64)?;
+        let mut done = 0;
+        for (idx, start, len) in Dram::pieces(offset, bytes.len() as u64) {
+            self.page_mut(idx)[start..start + len].copy_from_slice(&bytes[done..done + len]);
+            done += len;
+        }
+        Ok(())
+    }
+
+    // Zero fills [addr, addr + size). Whole pages are released instead of
+    // being cleared.
+    fn zero(&mut self, addr: u64, size: u64) -> Result<(), MemError> {
+        let offset = self.offset(addr, size)?;
+        for (idx, start, len) in Dram::pieces(offset, size) {
+            if len == INDEX_SIZE {
+                self.drop_page(idx);
+            } else if self.page(idx).is_some() {
+                memset(&mut self.page_mut(idx)[start..start + len], 0);
+            }
+        }
+        Ok(())
+    }
+
+    // Reads `size` bytes little endian.
+    fn read_le(&self, addr: u64, size: u64) -> Result<u64, MemError> {
+        let mut bytes = [0; 8];
+        self.read_bytes(addr, &mut bytes[..size as usize])?;
+        Ok(u64::from_le_bytes(bytes))
+    }
+
+    fn write_le(&mut self, addr: u64, val: u64, size: u64) -> Result<(), MemError> {
+        self.write_bytes(addr, &val.to_le_bytes()[..size as usize])
+    }
+
+    // Every store goes through here, so the page flags always apply.
+    fn store_le(&mut self, addr: u64, val: u64, size: u64) -> Result<(), MemError> {
+        self.check_write(addr, size)?;
+        self.write_le(addr, val, size)?;
+        self.mark_dirty(addr, size);
+        Ok(())
+    }
+
+    fn check_executable(&self, addr: u64, size: u64) -> Result<(), MemError> {
+        let (idx, idx_end) = self.get_indices(addr, size)?;
+        if (idx..=idx_end).all(|idx| self.flag(idx) & EXECUTABLE != 0) {
+            Ok(())
+        } else {
+            Err(MemError::NotExecutable)
+        }
+    }
+
+    // Marks the pages of a checked write as modified.
+    fn mark_dirty(&mut self, addr: u64, size: u64) {
+        if let Ok((idx, idx_end)) = self.get_indices(addr, size) {
+            for idx in idx..=idx_end {
+                *self.flag_mut(idx) |= DIRTY;
+            }
+        }
     }
 }
 
-#[derive(Debug, Clone)]
+// Errors of memory accesses. The hart raises them as the access fault of
+// the access that failed: AccessFault for fetches, LoadAccessFault for
+// loads and StoreAMOAccessFault for stores.
+#[derive(Debug, Clone, PartialEq)]
 pub enum MemError {
     OutOfBounds,
     LoadAccessFault,
-    StoreAMOAccessFault
+    StoreAMOAccessFault,
+    // A write to a FROZEN page.
+    Frozen,
+    // A write to an EXECUTABLE page.
+    NotWritable,
+    // A fetch from a page that isn't EXECUTABLE.
+    NotExecutable,
+    // A MemoryLayout whose regions are misaligned or don't fit.
+    InvalidLayout,
+    // A snapshot that was already restored or committed.
+    InvalidSnapshot,
 }
 
 impl Display for MemError {
@@ -95,38 +531,38 @@ impl Memory for Dram {
     type Bytes = Vec<u8>;
     type Error = MemError;
 
+    // Initializes [addr, addr + size): the first `offset` bytes and whatever
+    // `source` does not cover are zero filled, and every page touched by the
+    // region gets `flags`.
     fn init(&mut self, addr: u64, size: u64, flags: u8, source: Option<Self::Bytes>, offset: u64) -> Result<(), Self::Error> {
-        let mut written = 0;
-        if offset > 0 {
-            let actual = std::cmp::min(size, offset);
-            self.writeb(addr, actual);
-            written += actual;
+        if size == 0 {
+            return Ok(());
         }
+        let (idx, idx_end) = self.get_indices(addr, size)?;
+        self.zero(addr, size)?;
         if let Some(src) = source {
-            let actual = std::cmp::min(size - written, src.len() as u64);
-            if actual > 0 {
-                self.write_array((addr + written), src[0..actual as usize].to_vec());
-                written += actual;
-            }
+            let offset = std::cmp::min(offset, size);
+            let actual = std::cmp::min(size - offset, src.len() as u64) as usize;
+            self.write_bytes(addr + offset, &src[..actual])?;
         }
-        if written < size {
-            self.writeb(addr + written, size - written);
+        for i in idx..=idx_end {
+            *self.flag_mut(i) = flags;
         }
 
         Ok(())
     }
 
     fn get_flag(&mut self, idx: u64) -> Result<u8, Self::Error> {
-        if idx < INDICES as u64 {
-            Ok(self.flags[idx as usize])
+        if idx < self.layout.pages() as u64 {
+            Ok(self.flag(idx))
         } else {
             Err(MemError::OutOfBounds)
         }
     }
 
     fn set_flag(&mut self, idx: u64, flag: u8) -> Result<(), Self::Error> {
-        if idx < INDICES as u64 {
-            self.flags[idx as usize] |= flag;
+        if idx < self.layout.pages() as u64 {
+            *self.flag_mut(idx) |= flag;
             Ok(())
         } else {
             Err(MemError::OutOfBounds)
@@ -134,82 +570,82 @@ impl Memory for Dram {
     }
 
     fn clear_flag(&mut self, idx: u64, flag: u8) -> Result<(), Self::Error> {
-        if idx < INDICES as u64 {
-            self.flags[idx as usize] &= !flag;
+        if idx < self.layout.pages() as u64 {
+            if self.flag(idx) & flag != 0 {
+                *self.flag_mut(idx) &= !flag;
+            }
             Ok(())
         } else {
             Err(MemError::OutOfBounds)
         }
     }
 
-    fn get_indices(addr: u64, size: u64) -> Result<(u64, u64), Self::Error> {
-        let (end, overflow) = addr.overflowing_add(size);
-        if overflow {
-            return Err(MemError::OutOfBounds);
-        }
-
-        if end > MAX_MEM as u64 {
-            return Err(MemError::OutOfBounds);
-        }
-
-        let idx = addr >> INDEX_SHIFTS;
-        let idx_end = (end - 1) >> INDEX_SHIFTS;
+    // Page indices count from the start of the memory, not from address 0.
+    fn get_indices(&self, addr: u64, size: u64) -> Result<(u64, u64), Self::Error> {
+        // Even an empty access names the page it starts in.
+        let offset = self.offset(addr, size.max(1))?;
+        let idx = offset >> INDEX_SHIFTS;
+        let idx_end = (offset + size.max(1) - 1) >> INDEX_SHIFTS;
 
         Ok((idx, idx_end))
     }
 
-    fn execute_readhw(&mut self, addr: u64) -> Self::RegValue { 
+    fn check_write(&self, addr: u64, size: u64) -> Result<(), Self::Error> {
+        let (idx, idx_end) = self.get_indices(addr, size)?;
+        for flag in (idx..=idx_end).map(|idx| self.flag(idx)) {
+            if flag & FROZEN != 0 {
+                return Err(MemError::Frozen);
+            }
+            if flag & EXECUTABLE != 0 {
+                return Err(MemError::NotWritable);
+            }
+        }
+        Ok(())
+    }
+
+    fn execute_readhw(&mut self, addr: u64) -> Result<Self::RegValue, Self::Error> {
+        self.check_executable(addr, 2)?;
         self.readhw(&addr)
     }
 
-    fn execute_readw(&mut self, addr: u64) -> Self::RegValue {
+    fn execute_readw(&mut self, addr: u64) -> Result<Self::RegValue, Self::Error> {
+        self.check_executable(addr, 4)?;
         self.readw(&addr)
     }
     
     fn read(&self, addr: &Self::RegValue, size: u8) -> Result<Self::RegValue, Self::Error> {
         match size {
-            BYTE => {
-                Ok(self.readb(addr))
-            },
-            HALFWORD => {
-                Ok(self.readhw(addr))
-            },
-            WORD => {
-                Ok(self.readw(addr))
-            },
-            DOUBLEWORD => {
-                Ok(self.readdw(addr))
-            },
-            _ => return Err(MemError::LoadAccessFault)
+            BYTE => self.readb(addr),
+            HALFWORD => self.readhw(addr),
+            WORD => self.readw(addr),
+            DOUBLEWORD => self.readdw(addr),
+            _ => Err(MemError::LoadAccessFault),
         }
     }
 
-    fn readb(&self, addr: &Self::RegValue) -> Self::RegValue {
-        let idx: usize = *addr as usize;
-        self.mem[idx] as u64
+    fn readb(&self, addr: &Self::RegValue) -> Result<Self::RegValue, Self::Error> {
+        self.read_le(*addr, 1)
     }
-    fn readhw(&self, addr: &Self::RegValue) -> Self::RegValue {
-        let idx: usize = *addr as usize;
-        return (self.mem[idx] as u64) | ((self.mem[idx + 1] as u64) << 8);
+
+    fn readhw(&self, addr: &Self::RegValue) -> Result<Self::RegValue, Self::Error> {
+        self.read_le(*addr, 2)
+    }
+
+    fn readw(&self, addr: &Self::RegValue) -> Result<Self::RegValue, Self::Error> {
+        self.read_le(*addr, 4)
     }
-    fn readw(&self, addr: &Self::RegValue) -> Self::RegValue {
-        let idx: usize = *addr as usize;
-        return (self.mem[idx] as u64) | 
-            ((self.mem[idx + 1] as u64) << 8) | 
-            ((self.mem[idx + 2] as u64) << 16) | 
-            ((self.mem[idx + 3] as u64) << 24);
+
+    fn readdw(&self, addr: &Self::RegValue) -> Result<Self::RegValue, Self::Error> {
+        self.read_le(*addr, 8)
     }
 
-    fn readdw(&self, addr: &Self::RegValue) -> Self::RegValue {
-        let idx: usize = *addr as usize;
-        return (self.mem[idx] as u64) |
-            ((self.mem[idx + 1] as u64) << 8) |
-            ((self.mem[idx + 2] as u64) << 16) |
-            ((self.mem[idx + 3] as u64) << 24) |
-            ((self.mem[idx + 4] as u64) << 32) |
-            ((self.mem[idx + 5] as u64) << 40) |
-            ((self.mem[idx + 6] as u64) << 48) |
-            ((self.mem[idx + 7] as u64) << 56)        
+    fn read_array(&self, addr: Self::RegValue, size: u64) -> Result<Self::Bytes, Self::Error> {
+        if size == 0 {
+            return Ok(vec![]);
+        }
+        let mut bytes = vec![0; self.offset(addr, size).map(|_| size as usize)?];
+        self.read_bytes(addr, &mut bytes)?;
+        Ok(bytes)
     }
 
     fn write_array(&mut self, addr: Self::RegValue, value: Self::Bytes) -> Result<(), Self::Error> {
@@ -217,53 +653,35 @@ impl Memory for Dram {
         if size == 0 {
             return Ok(());
         }
-        let indices = Self::get_indices(addr, size)?;
-        self.set_flag(addr, DIRTY);
-        let arr = &mut self.mem[addr as usize..(addr + size) as usize];
-        arr.copy_from_slice(&value);
-        Ok(())
+        self.check_write(addr, size)?;
+        self.mark_dirty(addr, size);
+        self.write_bytes(addr, &value)
     }
 
     fn write(&mut self, addr: u64, value: u64, size: u8) -> Result<(), Self::Error> {
         match size {
-            BYTE => { self.writeb(addr, value) },
-            HALFWORD => { self.writehw(addr, value) },
-            WORD => { self.writew(addr, value) },
-            DOUBLEWORD => { self.writedw(addr, value) },
-            _ => return Err(MemError::StoreAMOAccessFault),
+            BYTE => self.writeb(addr, value),
+            HALFWORD => self.writehw(addr, value),
+            WORD => self.writew(addr, value),
+            DOUBLEWORD => self.writedw(addr, value),
+            _ => Err(MemError::StoreAMOAccessFault),
         }
-        Ok(())    
     }
-    fn writeb(&mut self, addr: u64, val: u64) {
-        let idx: usize = addr as usize;
-        self.mem[idx] = val as u8;
+
+    fn writeb(&mut self, addr: u64, val: u64) -> Result<(), Self::Error> {
+        self.store_le(addr, val, 1)
     }
 
-    fn writehw(&mut self, addr: u64, val: u64) {
-        let idx: usize = addr as usize;
-        self.mem[idx] = (val & 0xff) as u8;
-        self.mem[idx + 1] = ((val >> 8) & 0xff) as u8;
+    fn writehw(&mut self, addr: u64, val: u64) -> Result<(), Self::Error> {
+        self.store_le(addr, val, 2)
     }
-    
-    fn writew(&mut self, addr: u64, val: u64) {
-        let idx: usize = addr as usize;
-        self.mem[idx] = (val & 0xff) as u8;
-        self.mem[idx + 1] = ((val >> 8) & 0xff) as u8;
-        self.mem[idx + 2] = ((val >> 16) & 0xff) as u8;
-        self.mem[idx + 3] = ((val >> 24) & 0xff) as u8;
+
+    fn writew(&mut self, addr: u64, val: u64) -> Result<(), Self::Error> {
+        self.store_le(addr, val, 4)
     }
-    
-    fn writedw(&mut self, addr: u64, val: u64) {
-        let idx: usize = addr as usize;
-        self.mem[idx] = (val & 0xff) as u8;
-        self.mem[idx + 1] = ((val >> 8) & 0xff) as u8;
-        self.mem[idx + 2] = ((val >> 16) & 0xff) as u8;
-        self.mem[idx + 3] = ((val >> 24) & 0xff) as u8;
-        self.mem[idx + 4] = ((val >> 32) & 0xff) as u8;
-        self.mem[idx + 5] = ((val >> 40) & 0xff) as u8;
-        self.mem[idx + 6] = ((val >> 48) & 0xff) as u8;
-        self.mem[idx + 7] = ((val >> 56) & 0xff) as u8;
 
+    fn writedw(&mut self, addr: u64, val: u64) -> Result<(), Self::Error> {
+        self.store_le(addr, val, 8)
     }
     
     fn into_u64(&self, val: &Self::RegValue) -> u64 {
@@ -285,11 +703,7 @@ impl Memory for Dram {
 
 impl Default for Dram {
     fn default() -> Dram {
-        Dram {
-            mem: vec![0; MAX_MEM],
-            flags: vec![0; INDICES],
-            size: 0
-        }
+        Dram::new(MemoryLayout::default())
     }
 }
 
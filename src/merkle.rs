@@ -0,0 +1,147 @@
+use std::collections::{HashMap, HashSet};
+use sha2::{Digest, Sha256};
+use crate::consts::{FRAME_INDEX_SHIFTS, INDEX_SIZE};
+use crate::memory::{Dram, MemError};
+
+pub type Hash = [u8; 32];
+
+// Domain separation between page leaves and inner nodes, so a page can't
+// pass for a pair of hashes.
+const LEAF_PREFIX: u8 = 0;
+const NODE_PREFIX: u8 = 1;
+
+pub fn hash_page(data: &[u8]) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([LEAF_PREFIX]);
+    hasher.update(data);
+    hasher.finalize().into()
+}
+
+pub fn hash_node(left: &Hash, right: &Hash) -> Hash {
+    let mut hasher = Sha256::new();
+    hasher.update([NODE_PREFIX]);
+    hasher.update(left);
+    hasher.update(right);
+    hasher.finalize().into()
+}
+
+/// The sibling hashes on the path from a page to the root, bottom first.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Proof {
+    pub page: u64,
+    pub siblings: Vec<Hash>,
+}
+
+impl Proof {
+    /// Checks that `data`, the contents of the proven page, is part of the
+    /// memory committed to by `root`.
+    pub fn verify(&self, root: &Hash, data: &[u8]) -> bool {
+        if data.len() != INDEX_SIZE || self.siblings.len() >= 64 || self.page >> self.siblings.len() != 0 {
+            return false;
+        }
+        let hash = self.siblings.iter().enumerate().fold(hash_page(data), |hash, (level, sibling)| {
+            if (self.page >> level) & 1 == 0 {
+                hash_node(&hash, sibling)
+            } else {
+                hash_node(sibling, &hash)
+            }
+        });
+        hash == *root
+    }
+}
+
+/// A Merkle commitment to the contents of a `Dram`. Pages are the leaves,
+/// so every FRAME of pages is a subtree whose root commits to the frame,
+/// and the tree is padded to a power of two with pages of zeros.
+///
+/// The tree is sparse like the memory: only the nodes above pages that
+/// were written are stored, the others are the hashes of zero subtrees.
+/// `update` rehashes just the pages written since the previous update.
+#[derive(Debug, Clone)]
+pub struct MemoryCommitment {
+    height: u32,
+    // Nodes by (level, index), level 0 being the pages.
+    nodes: HashMap<(u32, u64), Hash>,
+    // The hash of a subtree of zeros at every level.
+    zeros: Vec<Hash>,
+}
+
+impl MemoryCommitment {
+    /// Commits to the current contents of `mem`. Later changes are picked
+    /// up by `update`, there should be a single commitment per memory.
+    pub fn new(mem: &mut Dram) -> MemoryCommitment {
+        let height = mem.layout().pages().next_power_of_two().trailing_zeros();
+        let mut zeros = vec![hash_page(&[0; INDEX_SIZE])];
+        for level in 0..height as usize {
+            zeros.push(hash_node(&zeros[level], &zeros[level]));
+        }
+        let mut commitment = MemoryCommitment {
+            height,
+            nodes: HashMap::new(),
+            zeros,
+        };
+        mem.take_modified();
+        let pages: HashSet<usize> = mem.resident().collect();
+        commitment.rehash(mem, pages);
+        commitment
+    }
+
+    /// Brings the commitment up to date with `mem` and returns the new root.
+    pub fn update(&mut self, mem: &mut Dram) -> Hash {
+        let pages = mem.take_modified();
+        self.rehash(mem, pages);
+        self.root()
+    }
+
+    pub fn root(&self) -> Hash {
+        self.node(self.height, 0)
+    }
+
+    /// The root of the subtree of FRAME `frame`.
+    pub fn frame_root(&self, frame: u64) -> Hash {
+        let level = std::cmp::min(FRAME_INDEX_SHIFTS as u32, self.height);
+        self.node(level, frame)
+    }
+
+    /// Proves the contents of page `page` against the root.
+    pub fn prove(&self, page: u64) -> Result<Proof, MemError> {
+        if page >> self.height != 0 {
+            return Err(MemError::OutOfBounds);
+        }
+        let siblings = (0..self.height).map(|level| self.node(level, (page >> level) ^ 1)).collect();
+        Ok(Proof { page, siblings })
+    }
+
+    fn node(&self, level: u32, idx: u64) -> Hash {
+        self.nodes.get(&(level, idx)).copied().unwrap_or(self.zeros[level as usize])
+    }
+
+    // Rehashes `pages` and then their ancestors one level at a time, so a
+    // node shared by several pages is only hashed once.
+    fn rehash(&mut self, mem: &Dram, pages: HashSet<usize>) {
+        let mut changed: HashSet<u64> = HashSet::new();
+        for idx in pages {
+            let hash = mem.page_data(idx).map_or(self.zeros[0], |data| hash_page(data));
+            self.set(0, idx as u64, hash);
+            changed.insert(idx as u64 >> 1);
+        }
+        for level in 1..=self.height {
+            let mut parents = HashSet::new();
+            for idx in changed {
+                let hash = hash_node(&self.node(level - 1, idx << 1), &self.node(level - 1, (idx << 1) | 1));
+                self.set(level, idx, hash);
+                parents.insert(idx >> 1);
+            }
+            changed = parents;
+        }
+    }
+
+    // Zero subtrees aren't stored.
+    fn set(&mut self, level: u32, idx: u64, hash: Hash) {
+        if hash == self.zeros[level as usize] {
+            self.nodes.remove(&(level, idx));
+        } else {
+            self.nodes.insert((level, idx), hash);
+        }
+    }
+}
@@ -0,0 +1,220 @@
+use std::collections::HashMap;
+use crate::csr::{
+    Privilege, MSTATUS_MXR, MSTATUS_SUM, SATP_ASID_MASK, SATP_ASID_SHIFT, SATP_MODE_SHIFT, SATP_MODE_SV39,
+    SATP_MODE_SV48, SATP_PPN_MASK,
+};
+use crate::exceptions::Exception;
+use crate::memory::{Memory, DOUBLEWORD};
+use crate::pmp::Pmp;
+
+pub const PAGE_SHIFT: u64 = 12;
+pub const PAGE_SIZE: u64 = 1 << PAGE_SHIFT;
+
+// Page table entry bits
+pub const PTE_V: u64 = 1 << 0;
+pub const PTE_R: u64 = 1 << 1;
+pub const PTE_W: u64 = 1 << 2;
+pub const PTE_X: u64 = 1 << 3;
+pub const PTE_U: u64 = 1 << 4;
+pub const PTE_G: u64 = 1 << 5;
+pub const PTE_A: u64 = 1 << 6;
+pub const PTE_D: u64 = 1 << 7;
+pub const PTE_PPN_SHIFT: u64 = 10;
+// Bits 63:54 are reserved for extensions this hart doesn't implement.
+const PTE_RESERVED: u64 = 0x3ff << 54;
+
+const PTE_SIZE: u64 = 8;
+const VPN_BITS: u64 = 9;
+const VPN_MASK: u64 = (1 << VPN_BITS) - 1;
+
+// Number of translations cached before the TLB is emptied.
+pub const TLB_ENTRIES: usize = 256;
+
+/// The kind of access being translated, it picks the permission that is
+/// checked and the exception raised when the translation fails.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AccessType {
+    Instruction,
+    Load,
+    Store,
+}
+
+impl AccessType {
+    pub fn page_fault(self, addr: u64) -> Exception {
+        match self {
+            AccessType::Instruction => Exception::InstructionPageFault(addr),
+            AccessType::Load => Exception::LoadPageFault(addr),
+            AccessType::Store => Exception::StoreAMOPageFault(addr),
+        }
+    }
+
+    pub fn access_fault(self) -> Exception {
+        match self {
+            AccessType::Instruction => Exception::AccessFault,
+            AccessType::Load => Exception::LoadAccessFault,
+            AccessType::Store => Exception::StoreAMOAccessFault,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+struct TlbEntry {
+    // The physical page the virtual page maps to. Superpages are cached
+    // one 4 KiB page at a time.
+    ppn: u64,
+    // The flag bits of the leaf entry.
+    flags: u64,
+}
+
+/// The hart state a translation depends on: the access privilege, the
+/// `satp` and `mstatus` registers and the PMP entries the walk is checked
+/// against.
+#[derive(Clone, Copy, Debug)]
+pub struct TranslationContext<'a> {
+    pub privilege: Privilege,
+    pub satp: u64,
+    pub mstatus: u64,
+    pub pmp: &'a Pmp,
+}
+
+/// The memory management unit of a hart: it translates virtual addresses
+/// through Sv39 or Sv48 page tables selected by `satp` and caches the
+/// translations in a software TLB until SFENCE.VMA flushes them.
+#[derive(Clone, Debug, Default)]
+pub struct Mmu {
+    // Translations by (asid, virtual page number).
+    tlb: HashMap<(u64, u64), TlbEntry>,
+}
+
+impl Mmu {
+    pub fn new() -> Mmu {
+        Mmu::default()
+    }
+
+    /// Translates `vaddr` for an access at `ctx.privilege`. Machine mode and
+    /// bare mode use physical addresses directly. The page table walk is
+    /// subject to `ctx.pmp` like any supervisor load.
+    pub fn translate<M: Memory<RegValue = u64>>(
+        &mut self,
+        mem: &M,
+        ctx: &TranslationContext,
+        vaddr: u64,
+        access: AccessType,
+    ) -> Result<u64, Exception> {
+        let TranslationContext { privilege, satp, mstatus, pmp } = *ctx;
+        let levels = match satp >> SATP_MODE_SHIFT {
+            SATP_MODE_SV39 => 3,
+            SATP_MODE_SV48 => 4,
+            _ => return Ok(vaddr),
+        };
+        if privilege == Privilege::Machine {
+            return Ok(vaddr);
+        }
+
+        let asid = (satp >> SATP_ASID_SHIFT) & SATP_ASID_MASK;
+        let vpn = vaddr >> PAGE_SHIFT;
+        let entry = match self.tlb.get(&(asid, vpn)) {
+            Some(entry) => *entry,
+            None => {
+                let entry = walk(mem, pmp, vaddr, access, levels, satp)?;
+                if self.tlb.len() >= TLB_ENTRIES {
+                    self.tlb.clear();
+                }
+                self.tlb.insert((asid, vpn), entry);
+                entry
+            },
+        };
+
+        if !permitted(entry.flags, access, privilege, mstatus) {
+            return Err(access.page_fault(vaddr));
+        }
+        Ok((entry.ppn << PAGE_SHIFT) | (vaddr & (PAGE_SIZE - 1)))
+    }
+
+    /// Drops cached translations as SFENCE.VMA does: all of them, those of
+    /// one virtual address, those of one address space, or both. Global
+    /// mappings are kept when only an address space is flushed.
+    pub fn flush(&mut self, vaddr: Option<u64>, asid: Option<u64>) {
+        let vpn = vaddr.map(|addr| addr >> PAGE_SHIFT);
+        self.tlb.retain(|&(entry_asid, entry_vpn), entry| {
+            let page = vpn.is_none_or(|vpn| vpn == entry_vpn);
+            let space = asid.is_none_or(|asid| asid == entry_asid && entry.flags & PTE_G == 0);
+            !(page && space)
+        });
+    }
+
+    pub fn len(&self) -> usize {
+        self.tlb.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tlb.is_empty()
+    }
+}
+
+// Walks the page table rooted at satp.PPN. Faults on invalid, reserved or
+// misaligned entries, and on entries without the accessed bit or, for
+// stores, the dirty bit: software keeps those bits up to date.
+fn walk<M: Memory<RegValue = u64>>(
+    mem: &M,
+    pmp: &Pmp,
+    vaddr: u64,
+    access: AccessType,
+    levels: u64,
+    satp: u64,
+) -> Result<TlbEntry, Exception> {
+    // The bits above the virtual address must copy its top bit.
+    let shift = 64 - (PAGE_SHIFT + VPN_BITS * levels);
+    if (((vaddr << shift) as i64) >> shift) as u64 != vaddr {
+        return Err(access.page_fault(vaddr));
+    }
+
+    let mut table = (satp & SATP_PPN_MASK) << PAGE_SHIFT;
+    for level in (0..levels).rev() {
+        let index = (vaddr >> (PAGE_SHIFT + level * VPN_BITS)) & VPN_MASK;
+        let pte_addr = table + index * PTE_SIZE;
+        let allowed = pmp.check(pte_addr, PTE_SIZE, AccessType::Load, Privilege::Supervisor);
+        if !allowed {
+            return Err(access.access_fault());
+        }
+        let pte = mem.read(&pte_addr, DOUBLEWORD).map_err(|_| access.access_fault())?;
+        if pte & PTE_V == 0 || (pte & PTE_R == 0 && pte & PTE_W != 0) || pte & PTE_RESERVED != 0 {
+            return Err(access.page_fault(vaddr));
+        }
+
+        let ppn = (pte >> PTE_PPN_SHIFT) & SATP_PPN_MASK;
+        if pte & (PTE_R | PTE_X) == 0 {
+            table = ppn << PAGE_SHIFT;
+            continue;
+        }
+
+        // A superpage must be aligned to its size.
+        let offset_mask = (1 << (level * VPN_BITS)) - 1;
+        if ppn & offset_mask != 0 {
+            return Err(access.page_fault(vaddr));
+        }
+        return Ok(TlbEntry {
+            ppn: ppn | ((vaddr >> PAGE_SHIFT) & offset_mask),
+            flags: pte & 0xff,
+        });
+    }
+    Err(access.page_fault(vaddr))
+}
+
+// Checks the leaf flags allow the access. Supervisor mode can only read
+// and write user pages with mstatus.SUM set and never executes them,
+// mstatus.MXR makes executable pages readable.
+fn permitted(flags: u64, access: AccessType, privilege: Privilege, mstatus: u64) -> bool {
+    let user = flags & PTE_U != 0;
+    let allowed = match privilege {
+        Privilege::User => user,
+        _ => !user || (access != AccessType::Instruction && mstatus & MSTATUS_SUM != 0),
+    };
+    let readable = flags & PTE_R != 0 || (mstatus & MSTATUS_MXR != 0 && flags & PTE_X != 0);
+    let granted = match access {
+        AccessType::Instruction => flags & PTE_X != 0,
+        AccessType::Load => readable,
+        AccessType::Store => flags & PTE_W != 0 && flags & PTE_D != 0,
+    };
+    allowed && granted && flags & PTE_A != 0
+}
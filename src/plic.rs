@@ -1,24 +1,228 @@
+use crate::bus::PLIC_BASE;
+use crate::memory::{MemError, WORD};
+
+// The platform level interrupt controller. Registers are given by absolute
+// address, an access to [START, END) reaches the register. Every context
+// has its enable bits ENABLE_CTX_OFFSET apart, and its threshold and claim
+// registers CTX_OFFSET apart.
 pub trait Plic: Default {
-    const SRC_PRIORITY_START: u64;
-    const SRC_PRIORITY_END: u64;
-    const PENDING_START: u64;
-    const PENDING_END: u64;
-    const ENABLE_START: u64;
-    const ENABLE_END: u64;
-    const TRESH_CLAIM_START: u64;
-    const THRES_CLAIM_END: u64;
-    const WORD_SIZE: u64;
-    const CTX_OFFSET: u64;
-    const SRC_NUM: u64;
+    const SRC_PRIORITY_START: u64 = PLIC_BASE;
+    const SRC_PRIORITY_END: u64 = PLIC_BASE + 0x1000;
+    const PENDING_START: u64 = PLIC_BASE + 0x1000;
+    const PENDING_END: u64 = PLIC_BASE + 0x1080;
+    const ENABLE_START: u64 = PLIC_BASE + 0x2000;
+    const ENABLE_END: u64 = PLIC_BASE + 0x20_0000;
+    const TRESH_CLAIM_START: u64 = PLIC_BASE + 0x20_0000;
+    const THRES_CLAIM_END: u64 = PLIC_BASE + 0x400_0000;
+    const WORD_SIZE: u64 = 4;
+    const CTX_OFFSET: u64 = 0x1000;
+    const ENABLE_CTX_OFFSET: u64 = 0x80;
+    const SRC_NUM: u64 = 1024;
     type Exception: std::error::Error;
 
     fn update_pending(&mut self, irq: u64);
     fn clear_pending(&mut self, irq: u64);
-    fn update_claim(&mut self, irq: u64);
-    fn is_enable(&self, ctx: u64, irq: u64);
+    // Claims the source for the context, as reading its claim register does.
+    fn update_claim(&mut self, ctx: u64, irq: u64);
+    fn is_enable(&self, ctx: u64, irq: u64) -> bool;
+    // Whether the context has an interrupt to claim.
+    fn is_interrupting(&self, ctx: u64) -> bool;
     fn read(&self, addr: u64, size: u8) -> Result<u64, Self::Exception>;
-    fn write(&mut self, addr: u64, value: u64, size: u8) -> Result<(), Exception>;
+    fn write(&mut self, addr: u64, value: u64, size: u8) -> Result<(), Self::Exception>;
     fn new() -> Self {
         Self::default()
     }
 }
+
+/// A PLIC with `SRC_NUM` sources, source 0 being reserved, and two
+/// contexts per hart: 2 * hartid for machine mode and 2 * hartid + 1 for
+/// supervisor mode, as on the QEMU virt board.
+///
+/// A source that raises its interrupt stays pending until a context claims
+/// it, and is then ignored until that context completes it by writing the
+/// claimed id back, whether or not the source is still enabled for it. A
+/// context is offered the enabled pending source with the highest priority
+/// above its threshold, the lowest id on ties.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SoftPlic {
+    priority: Vec<u32>,
+    pending: Vec<u32>,
+    // Claimed and not completed yet, by context.
+    claimed: Vec<Vec<u32>>,
+    enable: Vec<Vec<u32>>,
+    threshold: Vec<u32>,
+}
+
+// Priorities and thresholds go from 0 to 7, 0 never interrupts.
+const PRIORITY_MASK: u64 = 0b111;
+// Offset of the claim register from the threshold register.
+pub const CLAIM_OFFSET: u64 = 4;
+
+fn bit(bits: &[u32], irq: u64) -> bool {
+    bits.get((irq / 32) as usize).is_some_and(|word| (word >> (irq % 32)) & 1 == 1)
+}
+
+fn set_bit(bits: &mut [u32], irq: u64, value: bool) {
+    if let Some(word) = bits.get_mut((irq / 32) as usize) {
+        if value {
+            *word |= 1 << (irq % 32);
+        } else {
+            *word &= !(1 << (irq % 32));
+        }
+    }
+}
+
+impl SoftPlic {
+    pub fn with_harts(harts: usize) -> SoftPlic {
+        let words = (Self::SRC_NUM / 32) as usize;
+        SoftPlic {
+            priority: vec![0; Self::SRC_NUM as usize],
+            pending: vec![0; words],
+            claimed: vec![vec![0; words]; 2 * harts],
+            enable: vec![vec![0; words]; 2 * harts],
+            threshold: vec![0; 2 * harts],
+        }
+    }
+
+    pub fn is_pending(&self, irq: u64) -> bool {
+        bit(&self.pending, irq)
+    }
+
+    // Whether a context claimed the source and didn't complete it yet.
+    pub fn is_claimed(&self, irq: u64) -> bool {
+        self.claimed.iter().any(|bits| bit(bits, irq))
+    }
+
+    /// The source the context would get by reading its claim register, 0
+    /// when there is none.
+    pub fn best(&self, ctx: u64) -> u64 {
+        let (Some(threshold), Some(enable)) = (self.threshold.get(ctx as usize), self.enable.get(ctx as usize)) else {
+            return 0;
+        };
+        let mut best = (0, *threshold);
+        // Runs every tick, so whole words without candidates are skipped.
+        for (word, bits) in self.pending.iter().enumerate() {
+            let mut bits = bits & enable[word];
+            while bits != 0 {
+                let irq = word as u64 * 32 + u64::from(bits.trailing_zeros());
+                if self.priority[irq as usize] > best.1 {
+                    best = (irq, self.priority[irq as usize]);
+                }
+                bits &= bits - 1;
+            }
+        }
+        best.0
+    }
+}
+
+impl Default for SoftPlic {
+    fn default() -> SoftPlic {
+        SoftPlic::with_harts(1)
+    }
+}
+
+impl Plic for SoftPlic {
+    type Exception = MemError;
+
+    // Source 0 doesn't exist, and a claimed source can't be raised again
+    // before it is completed.
+    fn update_pending(&mut self, irq: u64) {
+        if irq != 0 && !self.is_claimed(irq) {
+            set_bit(&mut self.pending, irq, true);
+        }
+    }
+
+    fn clear_pending(&mut self, irq: u64) {
+        set_bit(&mut self.pending, irq, false);
+    }
+
+    fn update_claim(&mut self, ctx: u64, irq: u64) {
+        if let Some(claimed) = self.claimed.get_mut(ctx as usize).filter(|_| irq != 0) {
+            set_bit(claimed, irq, true);
+            set_bit(&mut self.pending, irq, false);
+        }
+    }
+
+    fn is_enable(&self, ctx: u64, irq: u64) -> bool {
+        self.enable.get(ctx as usize).is_some_and(|bits| bit(bits, irq))
+    }
+
+    fn is_interrupting(&self, ctx: u64) -> bool {
+        self.best(ctx) != 0
+    }
+
+    // Registers are 32 bits wide, those of missing sources and contexts
+    // read as zero. Reading a claim register doesn't claim, see `Bus::load`.
+    fn read(&self, addr: u64, size: u8) -> Result<u64, Self::Exception> {
+        if size != WORD || !addr.is_multiple_of(4) {
+            return Err(MemError::OutOfBounds);
+        }
+        let val = match addr {
+            Self::SRC_PRIORITY_START..Self::SRC_PRIORITY_END => {
+                self.priority.get(((addr - Self::SRC_PRIORITY_START) / 4) as usize).copied().unwrap_or(0)
+            },
+            Self::PENDING_START..Self::PENDING_END => {
+                self.pending.get(((addr - Self::PENDING_START) / 4) as usize).copied().unwrap_or(0)
+            },
+            Self::ENABLE_START..Self::ENABLE_END => {
+                let ctx = (addr - Self::ENABLE_START) / Self::ENABLE_CTX_OFFSET;
+                let word = (addr - Self::ENABLE_START) % Self::ENABLE_CTX_OFFSET / 4;
+                self.enable.get(ctx as usize).and_then(|bits| bits.get(word as usize)).copied().unwrap_or(0)
+            },
+            Self::TRESH_CLAIM_START..Self::THRES_CLAIM_END => {
+                let ctx = (addr - Self::TRESH_CLAIM_START) / Self::CTX_OFFSET;
+                match (addr - Self::TRESH_CLAIM_START) % Self::CTX_OFFSET {
+                    0 => self.threshold.get(ctx as usize).copied().unwrap_or(0),
+                    CLAIM_OFFSET => self.best(ctx) as u32,
+                    _ => 0,
+                }
+            },
+            _ => 0,
+        };
+        Ok(u64::from(val))
+    }
+
+    // Pending bits are read only, writing an id to a claim register
+    // completes it.
+    fn write(&mut self, addr: u64, value: u64, size: u8) -> Result<(), Self::Exception> {
+        if size != WORD || !addr.is_multiple_of(4) {
+            return Err(MemError::OutOfBounds);
+        }
+        match addr {
+            Self::SRC_PRIORITY_START..Self::SRC_PRIORITY_END => {
+                let irq = (addr - Self::SRC_PRIORITY_START) / 4;
+                if irq != 0 {
+                    if let Some(priority) = self.priority.get_mut(irq as usize) {
+                        *priority = (value & PRIORITY_MASK) as u32;
+                    }
+                }
+            },
+            Self::ENABLE_START..Self::ENABLE_END => {
+                let ctx = (addr - Self::ENABLE_START) / Self::ENABLE_CTX_OFFSET;
+                let word = (addr - Self::ENABLE_START) % Self::ENABLE_CTX_OFFSET / 4;
+                if let Some(bits) = self.enable.get_mut(ctx as usize).and_then(|bits| bits.get_mut(word as usize)) {
+                    // Source 0 can't be enabled.
+                    *bits = if word == 0 { value as u32 & !1 } else { value as u32 };
+                }
+            },
+            Self::TRESH_CLAIM_START..Self::THRES_CLAIM_END => {
+                let ctx = (addr - Self::TRESH_CLAIM_START) / Self::CTX_OFFSET;
+                match (addr - Self::TRESH_CLAIM_START) % Self::CTX_OFFSET {
+                    0 => {
+                        if let Some(threshold) = self.threshold.get_mut(ctx as usize) {
+                            *threshold = (value & PRIORITY_MASK) as u32;
+                        }
+                    },
+                    CLAIM_OFFSET => {
+                        if let Some(claimed) = self.claimed.get_mut(ctx as usize) {
+                            set_bit(claimed, value, false);
+                        }
+                    },
+                    _ => {},
+                }
+            },
+            _ => {},
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,158 @@
+use crate::csr::Privilege;
+use crate::mmu::AccessType;
+
+pub const PMP_ENTRIES: usize = 64;
+
+// pmpcfg entry bits
+pub const PMP_R: u8 = 1 << 0;
+pub const PMP_W: u8 = 1 << 1;
+pub const PMP_X: u8 = 1 << 2;
+pub const PMP_A_SHIFT: u8 = 3;
+pub const PMP_A: u8 = 0b11 << PMP_A_SHIFT;
+pub const PMP_L: u8 = 1 << 7;
+
+// Address matching modes in the A field.
+pub const PMP_OFF: u8 = 0;
+pub const PMP_TOR: u8 = 1;
+pub const PMP_NA4: u8 = 2;
+pub const PMP_NAPOT: u8 = 3;
+
+// pmpaddr holds bits 55:2 of an address on RV64 and 33:2 on RV32.
+const PMP_ADDR_MASK_64: u64 = (1 << 54) - 1;
+const PMP_ADDR_MASK_32: u64 = 0xffff_ffff;
+
+/// The physical memory protection entries of a hart. Entries are checked
+/// in order and the first one that matches an access decides it. Machine
+/// mode is only constrained by locked entries. Unlike the spec, where an
+/// access from below machine mode fails when no entry matches, accesses
+/// are allowed while every entry is off so harts that never configure
+/// PMP keep working.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Pmp {
+    cfg: [u8; PMP_ENTRIES],
+    addr: [u64; PMP_ENTRIES],
+}
+
+impl Default for Pmp {
+    fn default() -> Pmp {
+        Pmp {
+            cfg: [0; PMP_ENTRIES],
+            addr: [0; PMP_ENTRIES],
+        }
+    }
+}
+
+impl Pmp {
+    pub fn new() -> Pmp {
+        Pmp::default()
+    }
+
+    pub fn cfg(&self, idx: usize) -> u8 {
+        self.cfg[idx]
+    }
+
+    pub fn addr(&self, idx: usize) -> u64 {
+        self.addr[idx]
+    }
+
+    // The entries packed in pmpcfg`reg`, 4 of them on RV32 and 8 on RV64.
+    fn cfg_entries(reg: usize, xlen: u32) -> std::ops::Range<usize> {
+        let per_reg = xlen as usize / 8;
+        let first = reg * 4;
+        first..first + per_reg
+    }
+
+    pub fn read_cfg(&self, reg: usize, xlen: u32) -> u64 {
+        Pmp::cfg_entries(reg, xlen)
+            .enumerate()
+            .fold(0, |val, (i, idx)| val | (u64::from(self.cfg[idx]) << (i * 8)))
+    }
+
+    // Writes the entries of pmpcfg`reg`. Locked entries keep their value,
+    // the reserved bits read as zero and so does W without R.
+    pub fn write_cfg(&mut self, reg: usize, val: u64, xlen: u32) {
+        for (i, idx) in Pmp::cfg_entries(reg, xlen).enumerate() {
+            if self.cfg[idx] & PMP_L != 0 {
+                continue;
+            }
+            let mut cfg = (val >> (i * 8)) as u8 & (PMP_R | PMP_W | PMP_X | PMP_A | PMP_L);
+            if cfg & PMP_R == 0 {
+                cfg &= !PMP_W;
+            }
+            self.cfg[idx] = cfg;
+        }
+    }
+
+    pub fn read_addr(&self, idx: usize) -> u64 {
+        self.addr[idx]
+    }
+
+    // Writes pmpaddr`idx`, unless its entry is locked or it is the bottom
+    // of a locked TOR range.
+    pub fn write_addr(&mut self, idx: usize, val: u64, xlen: u32) {
+        let locked = self.cfg[idx] & PMP_L != 0;
+        let next_locked = idx + 1 < PMP_ENTRIES
+            && self.cfg[idx + 1] & PMP_L != 0
+            && mode(self.cfg[idx + 1]) == PMP_TOR;
+        if locked || next_locked {
+            return;
+        }
+        let mask = if xlen == 64 { PMP_ADDR_MASK_64 } else { PMP_ADDR_MASK_32 };
+        self.addr[idx] = val & mask;
+    }
+
+    // The [start, end) range entry `idx` matches, None when it's off.
+    fn range(&self, idx: usize) -> Option<(u64, u64)> {
+        let addr = self.addr[idx];
+        match mode(self.cfg[idx]) {
+            PMP_TOR => {
+                let start = if idx == 0 { 0 } else { self.addr[idx - 1] << 2 };
+                Some((start, addr << 2))
+            },
+            PMP_NA4 => Some((addr << 2, (addr << 2) + 4)),
+            PMP_NAPOT => {
+                // The trailing ones of the address encode the size.
+                let ones = addr.trailing_ones();
+                let start = (addr & !((1u64 << ones) - 1)) << 2;
+                let size = 1u128 << (ones + 3);
+                let end = (u128::from(start) + size).min(u128::from(u64::MAX)) as u64;
+                Some((start, end))
+            },
+            _ => None,
+        }
+    }
+
+    /// Checks an access of `size` bytes at physical address `addr`.
+    pub fn check(&self, addr: u64, size: u64, access: AccessType, privilege: Privilege) -> bool {
+        let end = addr.saturating_add(size);
+        let mut active = false;
+        for idx in 0..PMP_ENTRIES {
+            let Some((start, stop)) = self.range(idx) else {
+                continue;
+            };
+            active = true;
+            if addr >= stop || end <= start {
+                continue;
+            }
+            // The entry has to cover the whole access.
+            if addr < start || end > stop {
+                return false;
+            }
+            let cfg = self.cfg[idx];
+            if privilege == Privilege::Machine && cfg & PMP_L == 0 {
+                return true;
+            }
+            let perm = match access {
+                AccessType::Instruction => PMP_X,
+                AccessType::Load => PMP_R,
+                AccessType::Store => PMP_W,
+            };
+            return cfg & perm != 0;
+        }
+        privilege == Privilege::Machine || !active
+    }
+}
+
+fn mode(cfg: u8) -> u8 {
+    (cfg & PMP_A) >> PMP_A_SHIFT
+}
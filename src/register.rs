@@ -1,5 +1,5 @@
 #![allow(unused, unused_mut, dead_code)]
-use std::ops::{BitAnd, BitOr, BitXor, Not, Shl, Shr};
+use std::ops::{Add, BitAnd, BitOr, BitXor, Div, Mul, Neg, Not, Shl, Shr, Sub};
 
 pub trait RegisterValue:
     Sized +
@@ -57,6 +57,36 @@ pub trait RegisterValue:
     fn rotater(&self, rhs: &Self) -> Self;
     fn zero_extend(&self, start: &Self) -> Self;
     fn sign_extend(&self, start: &Self) -> Self;
+    fn from_u64(val: u64) -> Self;
+    fn to_u64(&self) -> u64;
+    // The value sign extended from XLEN bits.
+    fn to_i64(&self) -> i64;
+}
+
+// The value held in a float register: f32 on a hart with only the F
+// extension, f64 once the registers are wide enough for D. Single precision
+// values are held by value, widened in f64 registers.
+pub trait FloatValue:
+    Copy +
+    PartialOrd +
+    Add<Output = Self> +
+    Sub<Output = Self> +
+    Mul<Output = Self> +
+    Div<Output = Self> +
+    Neg<Output = Self>
+{
+    const FLEN: u8;
+
+    fn zero() -> Self;
+    fn from_f32(val: f32) -> Self;
+    fn to_f32(&self) -> f32;
+    fn from_f64(val: f64) -> Self;
+    fn to_f64(&self) -> f64;
+    fn mul_add(&self, a: Self, b: Self) -> Self;
+    fn sqrt(&self) -> Self;
+    fn copysign(&self, sign: Self) -> Self;
+    fn min(&self, other: Self) -> Self;
+    fn max(&self, other: Self) -> Self;
 }
 
 // The basic register addresses
@@ -204,6 +234,45 @@ impl From<Register> for RegisterAbi {
     }
 }
 
+impl From<RegisterAbi> for Register {
+    fn from(reg: RegisterAbi) -> Register {
+        match reg {
+            RegisterAbi::Zero(_) => Register::X0,
+            RegisterAbi::Ra(_) => Register::X1,
+            RegisterAbi::Sp(_) => Register::X2,
+            RegisterAbi::Gp(_) => Register::X3,
+            RegisterAbi::Tp(_) => Register::X4,
+            RegisterAbi::T0(_) => Register::X5,
+            RegisterAbi::T1(_) => Register::X6,
+            RegisterAbi::T2(_) => Register::X7,
+            RegisterAbi::S0(_) => Register::X8,
+            RegisterAbi::S1(_) => Register::X9,
+            RegisterAbi::A0(_) => Register::X10,
+            RegisterAbi::A1(_) => Register::X11,
+            RegisterAbi::A2(_) => Register::X12,
+            RegisterAbi::A3(_) => Register::X13,
+            RegisterAbi::A4(_) => Register::X14,
+            RegisterAbi::A5(_) => Register::X15,
+            RegisterAbi::A6(_) => Register::X16,
+            RegisterAbi::A7(_) => Register::X17,
+            RegisterAbi::S2(_) => Register::X18,
+            RegisterAbi::S3(_) => Register::X19,
+            RegisterAbi::S4(_) => Register::X20,
+            RegisterAbi::S5(_) => Register::X21,
+            RegisterAbi::S6(_) => Register::X22,
+            RegisterAbi::S7(_) => Register::X23,
+            RegisterAbi::S8(_) => Register::X24,
+            RegisterAbi::S9(_) => Register::X25,
+            RegisterAbi::S10(_) => Register::X26,
+            RegisterAbi::S11(_) => Register::X27,
+            RegisterAbi::T3(_) => Register::X28,
+            RegisterAbi::T4(_) => Register::X29,
+            RegisterAbi::T5(_) => Register::X30,
+            RegisterAbi::T6(_) => Register::X31,
+        }
+    }
+}
+
 impl From<usize> for Register {
     fn from(i: usize) -> Register {
         assert!(i < 32);
@@ -573,4 +642,302 @@ impl RegisterValue for u64 {
         let start = std::cmp::min(*start, 64);
         (((*self << (64 - start)) as i64) >> (64 - start)) as u64 
     }
-}
\ No newline at end of file
+
+    fn from_u64(val: u64) -> Self {
+        val
+    }
+
+    fn to_u64(&self) -> u64 {
+        *self
+    }
+
+    fn to_i64(&self) -> i64 {
+        *self as i64
+    }
+}
+
+impl RegisterValue for u32 {
+    const BITS: u8 = 32;
+    const SHIFT_MASK: u8 = 0x1F;
+
+    fn zero() -> Self { 0 }
+    fn one() -> Self { 1 }
+    fn min_val() -> Self { u32::MIN }
+    fn max_val() -> Self { u32::MAX }
+
+    fn equal(&self, other: &Self) -> Self {
+        (self == other).into()
+    }
+
+    fn less_than(&self, other: &Self) -> Self {
+        (self < other).into()
+    }
+
+    fn less_than_signed(&self, other: &Self) -> Self {
+        ((*self as i32) < (*other as i32)).into()
+    }
+
+    fn not_equal(&self, rhs: &Self) -> Self {
+        self.equal(rhs).logical_not()
+    }
+
+    fn greater_equal(&self, rhs: &Self) -> Self {
+        self.less_than(rhs).logical_not()
+    }
+
+    fn greater_equal_signed(&self, rhs: &Self) -> Self {
+        self.less_than_signed(rhs).logical_not()
+    }
+
+    fn logical_not(&self) -> Self {
+        (*self != Self::one()).into()
+    }
+
+    fn condition(&self, tval: &Self, fval: &Self) -> Self {
+        if *self == Self::one() {
+            *tval
+        } else {
+            *fval
+        }
+    }
+
+    fn oflow_add(&self, rhs: &Self) -> Self {
+        (*self).overflowing_add(*rhs).0
+    }
+
+    fn oflow_sub(&self, rhs: &Self) -> Self {
+        (*self).overflowing_sub(*rhs).0
+    }
+
+    fn oflow_mul(&self, rhs: &Self) -> Self {
+        (*self).overflowing_mul(*rhs).0
+    }
+
+    fn oflow_div(&self, rhs: &Self) -> Self {
+        if *rhs == 0 {
+            Self::max_val()
+        } else {
+            (*self).overflowing_div(*rhs).0
+        }
+    }
+
+    fn oflow_div_euclid(&self, rhs: &Self) -> Self {
+        if *rhs == 0 {
+            Self::max_val()
+        } else {
+            (*self).overflowing_div_euclid(*rhs).0
+        }
+    }
+
+    fn oflow_div_signed(&self, rhs: &Self) -> Self {
+        if *rhs == 0 {
+            (-1i32) as u32
+        } else {
+            let (val, overflow) = (*self as i32).overflowing_div(*rhs as i32);
+            if overflow {
+                ((-1i32) as u32) << (<Self as RegisterValue>::BITS - 1)
+            } else {
+                val as u32
+            }
+        }
+    }
+
+    fn oflow_rem(&self, rhs: &Self) -> Self {
+        if *rhs == 0 {
+            *self
+        } else {
+            (*self).overflowing_rem(*rhs).0
+        }
+    }
+
+    fn oflow_rem_euclid(&self, rhs: &Self) -> Self {
+        if *rhs == 0 {
+            *self
+        } else {
+            (*self).overflowing_rem_euclid(*rhs).0
+        }
+    }
+
+    fn oflow_rem_signed(&self, rhs: &Self) -> Self {
+        if *rhs == 0 {
+            *self
+        } else {
+            let (val, overflow) = (*self as i32).overflowing_rem(*rhs as i32);
+            if overflow {
+                0
+            } else {
+                val as u32
+            }
+        }
+    }
+
+    fn oflow_mul_high_signed(&self, rhs: &Self) -> Self {
+        let a = i64::from(*self as i32);
+        let b = i64::from(*rhs as i32);
+        (a.wrapping_mul(b) >> 32) as u32
+    }
+
+    fn oflow_mul_high_unsigned(&self, rhs: &Self) -> Self {
+        let a = u64::from(*self);
+        let b = u64::from(*rhs);
+        (a.wrapping_mul(b) >> 32) as u32
+    }
+
+    fn oflow_mul_high_signed_unsigned(&self, rhs: &Self) -> Self {
+        let a = i64::from(*self as i32);
+        let b = i64::from(*rhs);
+        (a.wrapping_mul(b) >> 32) as u32
+    }
+
+    fn oflow_neg(&self) -> Self {
+        (*self).overflowing_neg().0
+    }
+
+    fn oflow_pow(&self, exp: u32) -> Self {
+        (*self).overflowing_pow(exp).0
+    }
+
+    fn oflow_shl(&self, bits: u32) -> Self {
+        (*self).overflowing_shl(bits).0
+    }
+
+    fn oflow_shr(&self, bits: u32) -> Self {
+        (*self).overflowing_shr(bits).0
+    }
+
+    fn msb_zeros(&self) -> Self { self.leading_zeros() }
+    fn lsb_zeros(&self) -> Self { self.trailing_zeros() }
+    fn n_ones(&self) -> Self { self.count_ones() }
+
+    fn mul_no_carry(&self, rhs: &Self) -> Self {
+        let mut x: u32 = 0;
+        (0..32).for_each(|i| {
+            if ((rhs >> i) & 1) != 0 {
+                x ^= self << i
+            }
+        });
+
+        x
+    }
+
+    fn mul_no_carry_high(&self, rhs: &Self) -> Self {
+        let mut x: u32 = 0;
+        (1..32).for_each(|i| {
+            if ((rhs >> i) & 1) != 0 {
+                x ^= self >> (32 - i)
+            }
+        });
+
+        x
+    }
+
+    fn mul_no_carry_rev(&self, rhs: &Self) -> Self {
+        let mut x: u32 = 0;
+        (0..32).for_each(|i| {
+            if ((rhs >> i) & 1) != 0 {
+                x ^= self >> (31 - i);
+            }
+        });
+
+        x
+    }
+
+    fn of_no_carry_byte(&self) -> Self {
+        let mut rev_rem = 0;
+        if self & 0x0000_00ff != 0 {
+            rev_rem |= 0x0000_00ff
+        }
+
+        if self & 0x0000_ff00 != 0 {
+            rev_rem |= 0x0000_ff00
+        }
+
+        if self & 0x00ff_0000 != 0 {
+            rev_rem |= 0x00ff_0000
+        }
+
+        if self & 0xff00_0000 != 0 {
+            rev_rem |= 0xff00_0000
+        }
+
+        rev_rem
+    }
+
+    fn revb(&self) -> Self {
+        self.swap_bytes()
+    }
+
+    fn shl_signed(&self, bits: &Self) -> Self {
+        (*self as i32).wrapping_shl(*bits) as u32
+    }
+
+    fn shr_signed(&self, bits: &Self) -> Self {
+        (*self as i32).wrapping_shr(*bits) as u32
+    }
+
+    fn rotatel(&self, rhs: &Self) -> Self {
+        self.rotate_left(*rhs)
+    }
+
+    fn rotater(&self, rhs: &Self) -> Self {
+        self.rotate_right(*rhs)
+    }
+
+    fn zero_extend(&self, start: &Self) -> Self {
+        let start = std::cmp::min(*start, 32);
+        if start == 0 {
+            return 0;
+        }
+        (*self << (32 - start)) >> (32 - start)
+    }
+
+    fn sign_extend(&self, start: &Self) -> Self {
+        let start = std::cmp::min(*start, 32);
+        if start == 0 {
+            return 0;
+        }
+        (((*self << (32 - start)) as i32) >> (32 - start)) as u32
+    }
+
+    fn from_u64(val: u64) -> Self {
+        val as u32
+    }
+
+    fn to_u64(&self) -> u64 {
+        u64::from(*self)
+    }
+
+    fn to_i64(&self) -> i64 {
+        i64::from(*self as i32)
+    }
+}
+
+impl FloatValue for f32 {
+    const FLEN: u8 = 32;
+
+    fn zero() -> Self { 0.0 }
+    fn from_f32(val: f32) -> Self { val }
+    fn to_f32(&self) -> f32 { *self }
+    fn from_f64(val: f64) -> Self { val as f32 }
+    fn to_f64(&self) -> f64 { f64::from(*self) }
+    fn mul_add(&self, a: Self, b: Self) -> Self { f32::mul_add(*self, a, b) }
+    fn sqrt(&self) -> Self { f32::sqrt(*self) }
+    fn copysign(&self, sign: Self) -> Self { f32::copysign(*self, sign) }
+    fn min(&self, other: Self) -> Self { f32::min(*self, other) }
+    fn max(&self, other: Self) -> Self { f32::max(*self, other) }
+}
+
+impl FloatValue for f64 {
+    const FLEN: u8 = 64;
+
+    fn zero() -> Self { 0.0 }
+    fn from_f32(val: f32) -> Self { f64::from(val) }
+    fn to_f32(&self) -> f32 { *self as f32 }
+    fn from_f64(val: f64) -> Self { val }
+    fn to_f64(&self) -> f64 { *self }
+    fn mul_add(&self, a: Self, b: Self) -> Self { f64::mul_add(*self, a, b) }
+    fn sqrt(&self) -> Self { f64::sqrt(*self) }
+    fn copysign(&self, sign: Self) -> Self { f64::copysign(*self, sign) }
+    fn min(&self, other: Self) -> Self { f64::min(*self, other) }
+    fn max(&self, other: Self) -> Self { f64::max(*self, other) }
+}
@@ -1,16 +1,31 @@
 #![allow(unused, unused_mut, dead_code)]
 use crate::encoding::{EncodingTable, InstructionDecoder};
 use crate::encoding_types::Inst;
+use crate::compressed::{self, COMPRESSED_INST_LEN};
 use crate::extensions::{Base, Extension};
-use crate::exceptions::Exception;
+use crate::exceptions::{Exception, Trap};
+use crate::csr::{
+    CsrFile, Privilege, MSTATUS_MIE, MSTATUS_MPIE, MSTATUS_MPP, MSTATUS_MPP_SHIFT, MSTATUS_MPRV, MSTATUS_SIE,
+    MSTATUS_SPIE, MSTATUS_SPP, MSTATUS_TSR, MSTATUS_TVM, SATP,
+};
+use crate::mmu::{AccessType, Mmu, TranslationContext};
 use crate::instructions::Instruction;
-use crate::register::{Register, RegisterValue};
-use crate::memory::{Dram, MEM_SIZE};
+use crate::register::{FloatValue, Register, RegisterAbi, RegisterValue};
+use crate::syscall::{GuestMemory, Syscall, SyscallOutcome, Syscalls, SYSCALL_ARGS, SYSCALL_NUMBER, SYSCALL_RETURNS};
+use crate::memory::{Dram, MemoryLayout};
+use crate::consts::{N_REGISTERS, INDEX_SIZE, INDEX_SHIFTS, EXECUTABLE, WRITEABLE, DIRTY, FROZEN};
+use crate::gas::{CostSchedule, DEFAULT_GAS_LIMIT};
+use crate::elf::{Elf, ElfError, ELFCLASS32, ELFCLASS64, EM_RISCV, PF_W, PF_X};
 use crate::machine::{Machine, Support};
 use crate::memory::Memory;
+use crate::vm::{LoadError, LoadResult};
+use std::collections::BTreeMap;
 use std::error::Error;
+use std::num::FpCategory;
+use strum::EnumProperty;
 
 pub const INST_LEN: u64 = 4u64;
+pub const VERSION: u32 = 0;
 
 /// The software represeentation of the RISC-V HART aka Hardware Thread
 /// This is separated from the VM itself so that a VM with multiple SOFT's
@@ -20,12 +35,12 @@ pub const INST_LEN: u64 = 4u64;
 /// ```
 /// use trecho::encoding::{EncodingTable, InstructionDecoder};
 /// use trecho::register::{Register, RegisterValue};
-/// use trecho::memory::{Dram, MEM_SIZE, Memory};
+/// use trecho::memory::{Dram, Memory};
 /// use trecho::machine::{Machine, Support};
 /// use trecho::soft::SoftThread;
 ///
 /// let mut soft = SoftThread::<u64, f64, Dram>::default();
-/// let program = vec![0b1100_1100 as u8, 0b1100_1010 as u8, 0b1000_0101 as u8, 0b1001_0011 as u8];
+/// let program = vec![0b1001_0011 as u8, 0b1000_0101 as u8, 0b1100_1010 as u8, 0b1100_1100 as u8];
 /// soft.load_program(program);
 /// soft.execute();
 /// ```
@@ -35,229 +50,689 @@ pub struct SoftThread<R, F, M> {
     pub registers: [R; 33],
     pub f_registers: [F; 33],
     pub pc: R,
+    // Length of the instruction being executed, 2 if it was compressed.
+    inst_len: u64,
     pub program: Vec<u8>,
     pub remainder: u32,
     eq_flag: bool,
     enc_table: EncodingTable,
     pub bus: M,
-    pub csr: [R; 4096],
+    layout: MemoryLayout,
+    pub csr: CsrFile,
+    privilege: Privilege,
+    mmu: Mmu,
     pub res: Vec<u64>,
+    tval: u64,
+    next_pc: Option<R>,
+    cycles: u64,
+    max_cycles: u64,
+    running: bool,
+    reset_signal: bool,
+    syscalls: Vec<Box<dyn Syscalls>>,
+    costs: CostSchedule,
 }
 
-impl SoftThread<u64, f64, Dram> {
-    pub fn new(enc_table: EncodingTable) -> SoftThread<u64, f64, Dram> {
+impl<R: RegisterValue + Copy, F: FloatValue> SoftThread<R, F, Dram> {
+    // The hart starts at the base of `layout` with sp at the top of its
+    // stack.
+    pub fn new(enc_table: EncodingTable, layout: MemoryLayout) -> SoftThread<R, F, Dram> {
+        SoftThread::with_bus(enc_table, layout, Dram::new(layout))
+    }
+}
+
+impl<R: RegisterValue + Copy, F: FloatValue, M: Memory<RegValue = u64, Bytes = Vec<u8>>> SoftThread<R, F, M> {
+    // A hart running against `bus`, a `Dram` or a full `Bus` with devices,
+    // whose DRAM is described by `layout`.
+    pub fn with_bus(enc_table: EncodingTable, layout: MemoryLayout, bus: M) -> SoftThread<R, F, M> {
+        let csr = CsrFile::new(enc_table.misa() & !u64::from(Self::missing_letters()));
         let mut soft = SoftThread {
-            registers: [0; 33],
-            f_registers: [0.0; 33],
-            pc: 0,
+            registers: [R::zero(); 33],
+            f_registers: [F::zero(); 33],
+            pc: R::from_u64(layout.base),
+            inst_len: INST_LEN,
             program: vec![],
             remainder: 0,
             eq_flag: false,
             enc_table,
-            csr: [0; 4096],
-            bus: Dram::default(),
-            res: vec![]
+            csr,
+            privilege: Privilege::Machine,
+            mmu: Mmu::new(),
+            bus,
+            layout,
+            res: vec![],
+            tval: 0,
+            next_pc: None,
+            cycles: 0,
+            max_cycles: DEFAULT_GAS_LIMIT,
+            running: false,
+            reset_signal: false,
+            syscalls: vec![],
+            costs: CostSchedule::default(),
         };
 
-        soft.registers[2] = MEM_SIZE;
-        soft.registers[0] = 0;
+        soft.registers[2] = R::from_u64(layout.stack().end);
 
         soft
     }
 
-    pub(crate) fn read_xreg(&self, idx: usize) -> u64 {
+    pub fn layout(&self) -> &MemoryLayout {
+        &self.layout
+    }
+
+    // Registers a host handler for environment calls made by the guest.
+    pub fn add_syscall(&mut self, handler: Box<dyn Syscalls>) {
+        self.syscalls.push(handler);
+    }
+
+    pub fn set_cost_schedule(&mut self, costs: CostSchedule) {
+        self.costs = costs;
+    }
+
+    pub(crate) fn read_xreg(&self, idx: usize) -> R {
         self.registers[idx]
     }
 
-    pub(crate) fn read_freg(&self, idx: usize) -> f64 {
+    pub(crate) fn read_freg(&self, idx: usize) -> F {
         self.f_registers[idx]
     } 
 
+    // The misa letters of the encoding table this hart cannot implement: D
+    // and Q need 64 bit float registers.
+    fn missing_letters() -> u32 {
+        if F::FLEN < 64 {
+            1 << (b'd' - b'a') | 1 << (b'q' - b'a')
+        } else {
+            0
+        }
+    }
+
     pub(crate) fn advance(&mut self) {
-        self.pc += INST_LEN;
+        self.pc = R::from_u64(self.pc.to_u64().wrapping_add(self.inst_len));
+    }
+
+    // The alignment of instructions, 2 bytes once compressed instructions
+    // are enabled.
+    pub fn inst_align(&self) -> u64 {
+        if self.enc_table.compressed() {
+            COMPRESSED_INST_LEN
+        } else {
+            INST_LEN
+        }
+    }
+
+    // Reads the instruction at pc from the bus. Instructions can only be
+    // fetched from aligned addresses on EXECUTABLE pages. With compressed
+    // instructions enabled a 16 bit parcel is read first, and the rest of
+    // the instruction only if the parcel is not a complete instruction.
+    pub(crate) fn fetch(&mut self) -> Result<Inst, Exception> {
+        let pc = self.pc.to_u64();
+        if !pc.is_multiple_of(self.inst_align()) {
+            self.tval = pc;
+            return Err(Exception::AddressMisaligned);
+        }
+        let addr = self.translate(pc, AccessType::Instruction)?;
+        if self.enc_table.compressed() {
+            self.check_pmp(pc, addr, COMPRESSED_INST_LEN, AccessType::Instruction)?;
+            let parcel = self.fetch_parcel(pc, addr)? as u16;
+            if compressed::is_compressed(parcel) {
+                self.inst_len = COMPRESSED_INST_LEN;
+                return Ok(Inst::from(parcel));
+            }
+            // The upper half of the instruction may be on the next page,
+            // which need not follow this one in physical memory.
+            let upper_pc = pc + COMPRESSED_INST_LEN;
+            let upper = self.translate(upper_pc, AccessType::Instruction)?;
+            self.check_pmp(upper_pc, upper, COMPRESSED_INST_LEN, AccessType::Instruction)?;
+            if upper != addr + COMPRESSED_INST_LEN {
+                self.inst_len = INST_LEN;
+                return Ok(Inst::from(parcel) | (self.fetch_parcel(upper_pc, upper)? << 16));
+            }
+        }
+        self.check_pmp(pc, addr, INST_LEN, AccessType::Instruction)?;
+        self.inst_len = INST_LEN;
+        match self.bus.execute_readw(addr) {
+            Ok(inst) => Ok(inst as Inst),
+            Err(_) => {
+                self.tval = pc;
+                Err(Exception::AccessFault)
+            }
+        }
+    }
+
+    // Reads 16 bits of an instruction, only EXECUTABLE pages can be fetched.
+    fn fetch_parcel(&mut self, vaddr: u64, addr: u64) -> Result<Inst, Exception> {
+        match self.bus.execute_readhw(addr) {
+            Ok(parcel) => Ok(parcel as Inst),
+            Err(_) => {
+                self.tval = vaddr;
+                Err(Exception::AccessFault)
+            }
+        }
+    }
+
+    // The privilege an access is made at. With mstatus.MPRV set, loads and
+    // stores in machine mode use the privilege in mstatus.MPP.
+    fn access_privilege(&self, access: AccessType) -> Privilege {
+        let status = self.csr.mstatus;
+        if access != AccessType::Instruction && self.privilege == Privilege::Machine && status & MSTATUS_MPRV != 0 {
+            Privilege::from_bits((status & MSTATUS_MPP) >> MSTATUS_MPP_SHIFT)
+        } else {
+            self.privilege
+        }
+    }
+
+    // Translates a virtual address through the MMU.
+    pub(crate) fn translate(&mut self, vaddr: u64, access: AccessType) -> Result<u64, Exception> {
+        let ctx = TranslationContext {
+            privilege: self.access_privilege(access),
+            satp: self.csr.satp,
+            mstatus: self.csr.mstatus,
+            pmp: &self.csr.pmp,
+        };
+        let res = self.mmu.translate(&self.bus, &ctx, vaddr, access);
+        if res.is_err() {
+            self.tval = vaddr;
+        }
+        res
+    }
+
+    // Checks a physical access of `size` bytes against the PMP entries.
+    fn check_pmp(&mut self, vaddr: u64, addr: u64, size: u64, access: AccessType) -> Result<(), Exception> {
+        if self.csr.pmp.check(addr, size, access, self.access_privilege(access)) {
+            return Ok(());
+        }
+        self.tval = vaddr;
+        Err(access.access_fault())
+    }
+
+    // Flushes the TLB for sfence.vma. rs1 selects a virtual address and rs2
+    // an address space, x0 selects all of them.
+    pub(crate) fn sfence_vma(&mut self, rs1: Register, rs2: Register, inst: Inst) -> Result<(), Exception> {
+        let trapped = self.privilege == Privilege::Supervisor && self.csr.mstatus & MSTATUS_TVM != 0;
+        if self.privilege < Privilege::Supervisor || trapped {
+            return Err(Exception::Invalid(inst as u64));
+        }
+        let vaddr = (rs1 != Register::X0).then(|| self.registers[rs1 as usize].to_u64());
+        let asid = (rs2 != Register::X0).then(|| self.registers[rs2 as usize].to_u64());
+        self.mmu.flush(vaddr, asid);
+        self.advance();
+        Ok(())
+    }
+
+    // Fetches and decodes the instruction at pc and charges its gas. A
+    // failed fetch still costs an instruction, so a trap loop cannot run
+    // for free.
+    fn fetch_decode(&mut self) -> Result<(Inst, Instruction), Exception> {
+        match self.fetch() {
+            Ok(inst) => {
+                let instruction = if self.inst_len == COMPRESSED_INST_LEN {
+                    match compressed::expand(inst as u16, self.enc_table.get_base()) {
+                        instruction if self.enc_table.supports(&instruction) => instruction,
+                        _ => Instruction::Undefined,
+                    }
+                } else {
+                    Instruction::decode(inst, &self.enc_table)
+                };
+                self.add_cycles(self.costs.cost(&instruction))?;
+                Ok((inst, instruction))
+            },
+            Err(e) => {
+                self.add_cycles(self.costs.default)?;
+                Err(e)
+            },
+        }
+    }
+
+    // Completes an instruction: commits the staged pc on success, or
+    // delivers the exception to the guest as a trap.
+    fn retire(&mut self, res: Result<(), Exception>) -> Result<(), Exception> {
+        // x0 is hardwired to zero, discard whatever the instruction wrote to it.
+        self.registers[0] = R::zero();
+        self.csr.tick(res.is_ok(), self.bus.keeps_time());
+        match res {
+            Ok(()) => {
+                self.commit_pc();
+                Ok(())
+            },
+            Err(e) => match self.take_trap(&e) {
+                Trap::Fatal => Err(e),
+                _ => Ok(()),
+            },
+        }
+    }
+
+    pub fn privilege(&self) -> Privilege {
+        self.privilege
+    }
+
+    pub fn set_privilege(&mut self, privilege: Privilege) {
+        self.privilege = privilege;
+    }
+
+    /// Takes the exception as a trap into machine mode, or into supervisor
+    /// mode when it was raised below machine mode and its bit is set in
+    /// `medeleg`. The pc, cause and trap value are recorded in the
+    /// `xepc`/`xcause`/`xtval` CSRs of that mode, `mstatus` saves the
+    /// previous privilege and interrupt enable, and the pc is redirected
+    /// to the handler in `xtvec`. Exceptions without an architectural cause
+    /// code, or raised while no handler is installed, are fatal.
+    pub fn take_trap(&mut self, e: &Exception) -> Trap {
+        let tval = match e {
+            Exception::Invalid(inst) => *inst,
+            Exception::InstructionPageFault(addr)
+            | Exception::LoadPageFault(addr)
+            | Exception::StoreAMOPageFault(addr) => *addr,
+            Exception::Breakpoint => self.pc.to_u64(),
+            _ => self.tval,
+        };
+        self.tval = 0;
+
+        let cause = match e.code() {
+            Some(cause) => cause,
+            None => return Trap::Fatal,
+        };
+
+        let delegated = self.privilege != Privilege::Machine && (self.csr.medeleg >> cause) & 1 == 1;
+        if !self.enter_trap(cause, tval, delegated) {
+            return Trap::Fatal;
+        }
+
+        match e {
+            Exception::Breakpoint
+            | Exception::EnvironmentCallFromUMode
+            | Exception::EnvironmentCallFromSMode
+            | Exception::EnvironmentCallFromMMode => Trap::Requested,
+            _ => Trap::Contained,
+        }
+    }
+
+    /// Ticks the devices on the bus, then takes the highest priority
+    /// interrupt that is pending and enabled at the current privilege, if
+    /// any. The interrupted instruction resumes from `xepc` once the
+    /// handler returns. Returns whether an interrupt was taken.
+    pub fn take_interrupt(&mut self) -> bool {
+        self.bus.increment(&mut self.csr);
+        let Some((code, delegated)) = self.csr.pending_interrupt(self.privilege) else {
+            return false;
+        };
+        // Without a handler the interrupt stays pending.
+        self.enter_trap(code | 1 << (R::BITS - 1), 0, delegated)
     }
 
-    pub(crate) fn fetch(&self) -> Inst {
-        let mut bytes: [u8; 4] = [
-            self.program[(self.pc + 3) as usize],
-            self.program[(self.pc + 2) as usize],
-            self.program[(self.pc + 1) as usize],
-            self.program[self.pc as usize],
-        ];
-        let inst: Inst = u32::from_le_bytes(bytes);
-        return inst;
+    // Records the trap in the CSRs of the mode it goes to and jumps to the
+    // handler, vectored interrupts land at base + 4 * code. Returns false
+    // when no handler is installed.
+    fn enter_trap(&mut self, cause: u64, tval: u64, delegated: bool) -> bool {
+        let tvec = if delegated { self.csr.stvec } else { self.csr.mtvec };
+        let mut handler = tvec & !0b11;
+        // Without a handler the trap is fatal and the hart is left untouched.
+        if handler == 0 {
+            return false;
+        }
+        if cause >> (R::BITS - 1) == 1 && tvec & 0b11 == 1 {
+            handler += 4 * (cause & !(1 << (R::BITS - 1)));
+        }
+
+        let from = self.privilege;
+        let status = self.csr.mstatus;
+        if delegated {
+            self.csr.sepc = self.pc.to_u64();
+            self.csr.scause = cause;
+            self.csr.stval = tval;
+            let mut next = status & !(MSTATUS_SPP | MSTATUS_SPIE | MSTATUS_SIE);
+            if from == Privilege::Supervisor {
+                next |= MSTATUS_SPP;
+            }
+            if status & MSTATUS_SIE != 0 {
+                next |= MSTATUS_SPIE;
+            }
+            self.csr.mstatus = next;
+            self.privilege = Privilege::Supervisor;
+        } else {
+            self.csr.mepc = self.pc.to_u64();
+            self.csr.mcause = cause;
+            self.csr.mtval = tval;
+            let mut next = status & !(MSTATUS_MPP | MSTATUS_MPIE | MSTATUS_MIE);
+            next |= (from as u64) << MSTATUS_MPP_SHIFT;
+            if status & MSTATUS_MIE != 0 {
+                next |= MSTATUS_MPIE;
+            }
+            self.csr.mstatus = next;
+            self.privilege = Privilege::Machine;
+        }
+        self.pc = R::from_u64(handler);
+        true
+    }
+
+    // Returns from a machine mode trap handler: the privilege comes back
+    // from mstatus.MPP and MIE from MPIE, and execution resumes at mepc.
+    pub(crate) fn mret(&mut self, inst: Inst) -> Result<(), Exception> {
+        if self.privilege != Privilege::Machine {
+            return Err(Exception::Invalid(inst as u64));
+        }
+        let status = self.csr.mstatus;
+        self.privilege = Privilege::from_bits((status & MSTATUS_MPP) >> MSTATUS_MPP_SHIFT);
+        let mut next = (status & !(MSTATUS_MPP | MSTATUS_MIE)) | MSTATUS_MPIE;
+        if status & MSTATUS_MPIE != 0 {
+            next |= MSTATUS_MIE;
+        }
+        // Leaving machine mode clears MPRV.
+        if self.privilege != Privilege::Machine {
+            next &= !MSTATUS_MPRV;
+        }
+        self.csr.mstatus = next;
+        self.pc = R::from_u64(self.csr.mepc & !(self.inst_align() - 1));
+        Ok(())
+    }
+
+    // Returns from a supervisor mode trap handler, the counterpart of mret
+    // for sstatus.SPP/SPIE and sepc. mstatus.TSR traps it in supervisor mode.
+    pub(crate) fn sret(&mut self, inst: Inst) -> Result<(), Exception> {
+        let status = self.csr.mstatus;
+        let trapped = self.privilege == Privilege::Supervisor && status & MSTATUS_TSR != 0;
+        if self.privilege < Privilege::Supervisor || trapped {
+            return Err(Exception::Invalid(inst as u64));
+        }
+        self.privilege = if status & MSTATUS_SPP != 0 { Privilege::Supervisor } else { Privilege::User };
+        let mut next = (status & !(MSTATUS_SPP | MSTATUS_SIE | MSTATUS_MPRV)) | MSTATUS_SPIE;
+        if status & MSTATUS_SPIE != 0 {
+            next |= MSTATUS_SIE;
+        }
+        self.csr.mstatus = next;
+        self.pc = R::from_u64(self.csr.sepc & !(self.inst_align() - 1));
+        Ok(())
+    }
+
+    // Reads a CSR for a CSR instruction. Accesses to CSRs that don't
+    // exist or need a higher privilege raise an illegal instruction.
+    pub(crate) fn read_csr(&self, addr: usize, inst: Inst) -> Result<R, Exception> {
+        match self.csr.read(addr, self.privilege) {
+            Ok(val) => Ok(R::from_u64(val)),
+            Err(_) => Err(Exception::Invalid(inst as u64)),
+        }
+    }
+
+    // Writes a CSR for a CSR instruction, writes to read-only CSRs also
+    // raise an illegal instruction.
+    pub(crate) fn write_csr(&mut self, addr: usize, val: R, inst: Inst) -> Result<(), Exception> {
+        self.csr
+            .write(addr, val.to_u64(), self.privilege)
+            .map_err(|_| Exception::Invalid(inst as u64))?;
+        // Cached translations may belong to the previous page table.
+        if addr == SATP {
+            self.mmu.flush(None, None);
+        }
+        Ok(())
+    }
+
+    // Parses an ELF image and checks it targets this hart's base ISA.
+    fn parse_elf(&self, program: &[u8]) -> Result<Elf, Exception> {
+        let elf = Elf::parse(program).map_err(Exception::InvalidElf)?;
+        if elf.machine != EM_RISCV {
+            return Err(Exception::InvalidElf(ElfError::MachineMismatch(elf.machine)));
+        }
+        let class = match self.enc_table.get_base() {
+            Base::I32 => ELFCLASS32,
+            Base::I64 => ELFCLASS64,
+        };
+        if elf.class != class {
+            return Err(Exception::InvalidElf(ElfError::ClassMismatch));
+        }
+        Ok(elf)
+    }
+
+    // Writes the file backed bytes of every PT_LOAD segment at its virtual
+    // address and zero fills the rest of it (.bss), leaving the bytes around
+    // the segments alone. Pages with code are EXECUTABLE and the others
+    // WRITEABLE, so W^X holds: RWX segments and pages shared between code and
+    // data are rejected, and so are segments that don't fit below the heap.
+    fn load_segments(&mut self, program: &[u8], elf: &Elf) -> Result<(), Exception> {
+        let invalid = |_| Exception::InvalidElf(ElfError::SegmentOutOfBounds);
+        let size = elf.loadable().try_fold(0u64, |size, ph| size.checked_add(ph.memsz)).unwrap_or(u64::MAX);
+        if size > self.layout.code_size() {
+            return Err(Exception::InvalidElf(ElfError::Oversized(size)));
+        }
+        // The flags of every page the segments touch, and whether it has code.
+        let mut pages: BTreeMap<u64, (u8, bool)> = BTreeMap::new();
+        let mut segments = vec![];
+        for ph in elf.loadable().filter(|ph| ph.memsz > 0) {
+            let code = ph.flags & PF_X != 0;
+            if code && ph.flags & PF_W != 0 {
+                return Err(Exception::InvalidElf(ElfError::WritableCode));
+            }
+            let data = ph.data(program).map_err(Exception::InvalidElf)?;
+            let (idx, idx_end) = self.bus.get_indices(ph.vaddr, ph.memsz).map_err(invalid)?;
+            for idx in idx..=idx_end {
+                // Only a page already FROZEN stays so.
+                let flag = self.bus.get_flag(idx).map_err(invalid)? & FROZEN;
+                if pages.entry(idx).or_insert((flag, code)).1 != code {
+                    return Err(Exception::InvalidElf(ElfError::SharedCodePage));
+                }
+            }
+            segments.push((ph.vaddr, ph.memsz, data));
+        }
+
+        for (vaddr, memsz, data) in segments {
+            Memory::init(&mut self.bus, vaddr, memsz, WRITEABLE, Some(data.to_vec()), 0).map_err(invalid)?;
+        }
+        for (idx, (mut flag, code)) in pages {
+            if code {
+                flag |= EXECUTABLE;
+            }
+            self.bus.clear_flag(idx, u8::MAX).map_err(invalid)?;
+            self.bus.set_flag(idx, flag).map_err(invalid)?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn load(&mut self, addr: u64, size: u8) -> Result<u64, Exception> {
+        let vaddr = addr;
+        let addr = self.translate(vaddr, AccessType::Load)?;
+        self.check_pmp(vaddr, addr, u64::from(size / 8), AccessType::Load)?;
+        match self.bus.load(&addr, size) {
+            Ok(val) => Ok(val),
+            Err(_) => {
+                self.tval = vaddr;
+                Err(Exception::LoadAccessFault)
+            }
+        }
+    }
+
+    pub(crate) fn store(&mut self, addr: u64, value: u64, size: u8) -> Result<(), Exception> {
+        let vaddr = addr;
+        let addr = self.translate(vaddr, AccessType::Store)?;
+        self.check_pmp(vaddr, addr, u64::from(size / 8), AccessType::Store)?;
+        if self.bus.check_write(addr, u64::from(size / 8)).is_err() {
+            self.tval = vaddr;
+            return Err(Exception::StoreAMOAccessFault);
+        }
+        // The first store to a page grows the memory used by the program,
+        // the write marks it DIRTY.
+        if let Ok((idx, idx_end)) = self.bus.get_indices(addr, u64::from(size / 8)) {
+            for page in idx..=idx_end {
+                if self.bus.get_flag(page).is_ok_and(|flag| flag & DIRTY == 0) {
+                    self.add_cycles(self.costs.memory_page)?;
+                }
+            }
+        }
+        match self.bus.write(addr, value, size) {
+            Ok(()) => Ok(()),
+            Err(_) => {
+                self.tval = vaddr;
+                Err(Exception::StoreAMOAccessFault)
+            }
+        }
+    }
+
+    // Copies raw code into memory at the current pc and marks its pages
+    // executable. The code can be as large as the memory after the pc.
+    pub fn load_program(&mut self, code: Vec<u8>) -> LoadResult {
+        let size = code.len();
+        if size == 0 || !size.is_multiple_of(self.inst_align() as usize) {
+            return Err(LoadError::MalformedCode);
+        }
+
+        Memory::init(&mut self.bus, self.pc.to_u64(), size as u64, EXECUTABLE, Some(code.clone()), 0)
+            .map_err(|_| LoadError::OversizedCode(size))?;
+        self.program = code;
+        
+        Ok(())
+    }
+
+    // Places a piece of a larger program `offset` bytes past the pc on
+    // executable pages, see `Cpu::load_code`.
+    pub fn load_code_chunk(&mut self, offset: u64, chunk: Vec<u8>) -> Result<(), Exception> {
+        let addr = self.pc.to_u64().checked_add(offset).ok_or(Exception::InvalidAddr)?;
+        Memory::init(&mut self.bus, addr, chunk.len() as u64, EXECUTABLE, Some(chunk), 0)
+            .map_err(|_| Exception::InvalidAddr)
+    }
+}
+
+impl<R: RegisterValue + Copy, F: FloatValue, M: Memory<RegValue = u64, Bytes = Vec<u8>>> SoftThread<R, F, M> {
+    /// Fetches, decodes and executes a single instruction. Any exception raised
+    /// along the way is delivered to the guest as a trap (see `take_trap`), only
+    /// exceptions the guest cannot handle are returned to the caller. A pending
+    /// interrupt is taken instead of the instruction (see `take_interrupt`).
+    ///
+    /// RV32 and RV64 harts share this code: instructions that only exist on
+    /// RV64 raise an illegal instruction when XLEN is 32, and so do D and Q
+    /// when the float registers only hold single precision values.
+    pub fn execute(&mut self) -> Result<(), Exception> {
+        if self.take_interrupt() {
+            return Ok(());
+        }
+        let res = self.fetch_decode().and_then(|(inst, instruction)| self.step(inst, instruction));
+        self.retire(res)
     }
 
-    pub fn execute(&mut self) {
-        let instruction: Instruction = Instruction::decode(self.fetch(), &self.enc_table);
+    fn step(&mut self, inst: Inst, instruction: Instruction) -> Result<(), Exception> {
+        let rv64_only = instruction.get_str("Base") == Some("64");
+        let wide_float = matches!(instruction.get_str("Ext"), Some("D" | "Q"));
+        if (rv64_only && R::BITS < 64) || (wide_float && F::FLEN < 64) {
+            return Err(Exception::Invalid(inst as u64));
+        }
+
         match instruction {
             Instruction::Lui { rd, imm } => {
                 //load upper immediate
-                self.registers[rd as usize] = (imm as i64) as u64;
+                self.registers[rd as usize] = sext(i64::from(imm));
                 self.advance();
             },
             Instruction::Auipc { rd, imm } => {
                 //add upper immediate to program counter
-                if let Some(res) = self.pc.checked_add((imm as i64) as u64) {
-                    self.registers[rd as usize] = res
-                }
+                self.registers[rd as usize] = self.pc.oflow_add(&sext(i64::from(imm)));
                 self.advance();
             },
             Instruction::Jal { rd, imm } => {
                 // Jump and link
-                self.registers[rd as usize] = self.pc.wrapping_add(4);
-                self.pc = self.pc.wrapping_add((imm as i64) as u64);
+                self.registers[rd as usize] = self.pc.oflow_add(&R::from_u64(self.inst_len));
+                self.pc = self.pc.oflow_add(&sext(i64::from(imm)));
             },
             Instruction::Jalr { rd, rs1, imm } => {
                 // Jump and link register
-                let t = self.pc.wrapping_add(4);
-                self.pc = (self.registers[rs1 as usize].wrapping_add((imm as i64) as u64) & !1);
+                let t = self.pc.oflow_add(&R::from_u64(self.inst_len));
+                self.pc = self.registers[rs1 as usize].oflow_add(&sext(i64::from(imm))) & !R::one();
                 self.registers[rd as usize] = t;
             },
             Instruction::Beq { rs1, rs2, imm, .. } => {
-                // Branch if equal
-                if self.registers[rs1 as usize] == self.registers[rs2 as usize] {
-                    self.pc = self.pc.wrapping_add((imm as i64) as u64);
-                } else {
-                    self.advance();
-                }
+                let taken = self.registers[rs1 as usize].to_u64() == self.registers[rs2 as usize].to_u64();
+                self.branch(taken, imm);
             },
             Instruction::Bne { rs1, rs2, imm, .. } => {
-                // Branch if not equal
-                if self.registers[rs1 as usize] != self.registers[rs2 as usize] {
-                    self.pc = self.pc.wrapping_add((imm as i64) as u64);
-                } else {
-                    self.advance();
-                }
+                let taken = self.registers[rs1 as usize].to_u64() != self.registers[rs2 as usize].to_u64();
+                self.branch(taken, imm);
             },
             Instruction::Blt { rs1, rs2, imm, .. } => {
-                // Branch if less than
-                if (self.registers[rs1 as usize] as i64) < (self.registers[rs2 as usize] as i64) {
-                    self.pc = self.pc.wrapping_add((imm as i64) as u64);
-                } else {
-                    self.advance();
-                }
+                let taken = self.registers[rs1 as usize].to_i64() < self.registers[rs2 as usize].to_i64();
+                self.branch(taken, imm);
             },
             Instruction::Bge { rs1, rs2, imm, .. } => {
-                // Branch if greater or equal
-                if (self.registers[rs1 as usize] as i64) >= (self.registers[rs2 as usize] as i64) {
-                    self.pc = self.pc.wrapping_add((imm as i64) as u64);
-                } else {
-                    self.advance();
-                }
+                let taken = self.registers[rs1 as usize].to_i64() >= self.registers[rs2 as usize].to_i64();
+                self.branch(taken, imm);
             },
             Instruction::Bltu { rs1, rs2, imm, .. } => {
-                // Branch if less than unsigned
-                if self.registers[rs1 as usize] < self.registers[rs2 as usize] {
-                    self.pc = self.pc.wrapping_add((imm as i64) as u64);
-                } else { 
-                    self.advance(); 
-                }
+                let taken = self.registers[rs1 as usize].to_u64() < self.registers[rs2 as usize].to_u64();
+                self.branch(taken, imm);
             },
             Instruction::Bgeu { rs1, rs2, imm, .. } => {
-                // Branch if greater than unsigned
-                if self.registers[rs1 as usize] >= self.registers[rs2 as usize] {
-                    self.pc = self.pc.wrapping_add((imm as i64) as u64);
-                } else {
-                    self.advance();
-                }
+                let taken = self.registers[rs1 as usize].to_u64() >= self.registers[rs2 as usize].to_u64();
+                self.branch(taken, imm);
             },
             Instruction::Lb { rd, rs1, imm, .. } => {
-                let addr = self.registers[rs1 as usize].wrapping_add((imm as i64) as u64);
-                if let Ok(val) = self.bus.read(&addr.into(), 8) {
-                    self.registers[rd as usize] = ((self.bus.into_u64(&val)) as i64) as u64;
-                }
-
+                let val = self.load(self.addr(rs1, imm), 8)?;
+                self.registers[rd as usize] = sext(i64::from(val as i8));
                 self.advance();
             },
             Instruction::Lh { rd, rs1, imm, .. } => {
-                let addr = self.registers[rs1 as usize].wrapping_add((imm as i64) as u64);
-                if let Ok(val) = self.bus.read(&addr.into(), 16) {
-                    self.registers[rd as usize] = ((self.bus.into_u64(&val)) as i64) as u64;
-                }
-                
+                let val = self.load(self.addr(rs1, imm), 16)?;
+                self.registers[rd as usize] = sext(i64::from(val as i16));
                 self.advance();
             },
             Instruction::Lw { rd, rs1, imm, .. } => {
-                let addr = self.registers[rs1 as usize].wrapping_add((imm as i64) as u64);
-                if let Ok(val) = self.bus.read(&addr.into(), 32) {
-                    self.registers[rd as usize] = ((self.bus.into_u64(&val) as i32) as i64) as u64
-                }
-
+                let val = self.load(self.addr(rs1, imm), 32)?;
+                self.registers[rd as usize] = sext(i64::from(val as i32));
                 self.advance();
             },
             Instruction::Lbu { rd, rs1, imm, .. } => {
-                let addr = self.registers[rs1 as usize].wrapping_add((imm as i64) as u64);
-                if let Ok(val) = self.bus.read(&addr.into(), 8) {
-                    self.registers[rd as usize] = self.bus.into_u64(&val);
-                }
-
+                self.registers[rd as usize] = R::from_u64(self.load(self.addr(rs1, imm), 8)?);
                 self.advance();
             },
             Instruction::Lhu { rd, rs1, imm, .. } => {
-                let addr = self.registers[rs1 as usize].wrapping_add((imm as i64) as u64);
-                if let Ok(val) = self.bus.read(&addr.into(), 16) {
-                    self.registers[rd as usize] = self.bus.into_u64(&val);
-                }
-
+                self.registers[rd as usize] = R::from_u64(self.load(self.addr(rs1, imm), 16)?);
                 self.advance();
             },
             Instruction::Sb { rs1, rs2, imm, .. } => {
-                let addr = self.registers[rs1 as usize].wrapping_add((imm as i64) as u64);
-                let _ = self.bus.write(addr, self.registers[rs2 as usize], 8);
+                self.store(self.addr(rs1, imm), self.registers[rs2 as usize].to_u64(), 8)?;
                 self.advance();
             },
             Instruction::Sh { rs1, rs2, imm, .. } => {
-                let addr = self.registers[rs1 as usize].wrapping_add((imm as i64) as u64);
-                let _ = self.bus.write(addr, self.registers[rs2 as usize], 16);
+                self.store(self.addr(rs1, imm), self.registers[rs2 as usize].to_u64(), 16)?;
                 self.advance();
             },
             Instruction::Sw { rs1, rs2, imm, .. } => {
-                let addr = self.registers[rs1 as usize].wrapping_add((imm as i64) as u64);
-                let _ = self.bus.write(addr, self.registers[rs2 as usize], 32);
+                self.store(self.addr(rs1, imm), self.registers[rs2 as usize].to_u64(), 32)?;
                 self.advance();
             },
             Instruction::Addi { rd, rs1, imm, .. } => {
-                let imm = (imm as i64) as u64;
-                if let Some(res) = self.registers[rs1 as usize].checked_add(imm) {
-                    self.registers[rd as usize] = res;
-                }
+                self.registers[rd as usize] = self.registers[rs1 as usize].oflow_add(&sext(i64::from(imm)));
                 self.advance();
             },
             Instruction::Slti { rd, rs1, imm, .. } => {
-                self.registers[rd as usize] = if (self.registers[rs1 as usize] as i64) < (imm as i64) {
-                    1
-                } else {
-                    0
-                };
+                self.registers[rd as usize] = self.registers[rs1 as usize].less_than_signed(&sext(i64::from(imm)));
                 self.advance();
             },
             Instruction::Sltiu { rd, rs1, imm, .. } => {
-                self.registers[rd as usize] = if self.registers[rs1 as usize] < ((imm as i64) as u64) { 1 } else { 0 };
+                self.registers[rd as usize] = self.registers[rs1 as usize].less_than(&sext(i64::from(imm)));
                 self.advance();
             },
             Instruction::Xori { rd, rs1, imm, .. } => {
-                self.registers[rd as usize] = self.registers[rs1 as usize] ^ ((imm as i64) as u64);
+                self.registers[rd as usize] = self.registers[rs1 as usize] ^ sext(i64::from(imm));
                 self.advance();
             },
             Instruction::Ori { rd, rs1, imm, .. } => {
-                self.registers[rd as usize] = self.registers[rs1 as usize] | ((imm as i64) as u64);
+                self.registers[rd as usize] = self.registers[rs1 as usize] | sext(i64::from(imm));
                 self.advance();
             },
             Instruction::Andi { rd, rs1, imm, .. } => {
-                self.registers[rd as usize] = self.registers[rs1 as usize] & ((imm as i64) as u64);
+                self.registers[rd as usize] = self.registers[rs1 as usize] & sext(i64::from(imm));
                 self.advance();
             },
+            // Shift amounts of XLEN or more are reserved.
+            Instruction::Slli { shamt, .. }
+            | Instruction::Srli { shamt, .. }
+            | Instruction::Srai { shamt, .. } if shamt >= u32::from(R::BITS) => {
+                return Err(Exception::Invalid(inst as u64));
+            },
             Instruction::Slli { rd, rs1, shamt, .. } => {
-                self.registers[rd as usize] = self.registers[rs1 as usize].wrapping_shl(shamt);
+                self.registers[rd as usize] = self.registers[rs1 as usize].oflow_shl(shamt);
                 self.advance();
             },
             Instruction::Srli { rd, rs1, shamt, .. } => {
-                self.registers[rd as usize] = self.registers[rs1 as usize].wrapping_shr(shamt);
+                self.registers[rd as usize] = self.registers[rs1 as usize].oflow_shr(shamt);
                 self.advance();
             },
             Instruction::Srai { rd, rs1, shamt, .. } => {
-                self.registers[rd as usize] = (self.registers[rs1 as usize] as i64).wrapping_shr(shamt) as u64;
+                self.registers[rd as usize] = sext(self.registers[rs1 as usize].to_i64() >> shamt);
                 self.advance();
             },
             Instruction::Add { rd, rs1, rs2, .. } => {
@@ -269,24 +744,16 @@ impl SoftThread<u64, f64, Dram> {
                 self.advance();
             },
             Instruction::Sll { rd, rs1, rs2, .. } => {
-                let shamt = ((self.registers[rs2 as usize] & 0x3f) as u64) as u32;
-                self.registers[rd as usize] = self.registers[rs1 as usize].wrapping_shl(shamt);
+                let shamt = self.shamt(rs2);
+                self.registers[rd as usize] = self.registers[rs1 as usize].oflow_shl(shamt);
                 self.advance();
             },
             Instruction::Slt { rd, rs1, rs2, .. } => {
-                self.registers[rd as usize] = if ((self.registers[rs1 as usize] as i64) < (self.registers[rs2 as usize] as i64)) {
-                    1 
-                } else {
-                    0
-                };
+                self.registers[rd as usize] = self.registers[rs1 as usize].less_than_signed(&self.registers[rs2 as usize]);
                 self.advance();
             },
             Instruction::Sltu { rd, rs1, rs2, .. } => {
-                self.registers[rd as usize] = if self.registers[rs1 as usize] < self.registers[rs2 as usize] {
-                    1
-                } else {
-                    0
-                };
+                self.registers[rd as usize] = self.registers[rs1 as usize].less_than(&self.registers[rs2 as usize]);
                 self.advance();
             },
             Instruction::Xor { rd, rs1, rs2, .. } => {
@@ -294,13 +761,13 @@ impl SoftThread<u64, f64, Dram> {
                 self.advance();
             },
             Instruction::Srl { rd, rs1, rs2, .. } => {
-                let shamt = ((self.registers[rs2 as usize] & 0x3f) as u64) as u32;
-                self.registers[rd as usize] = self.registers[rs1 as usize].wrapping_shr(shamt);
+                let shamt = self.shamt(rs2);
+                self.registers[rd as usize] = self.registers[rs1 as usize].oflow_shr(shamt);
                 self.advance();
             },
             Instruction::Sra { rd, rs1, rs2, .. } => {
-                let shamt = ((self.registers[rs2 as usize] & 0x3f) as u64) as u32;
-                self.registers[rd as usize] = (self.registers[rs1 as usize] as i64).wrapping_shr(shamt) as u64;
+                let shamt = self.shamt(rs2);
+                self.registers[rd as usize] = sext(self.registers[rs1 as usize].to_i64() >> shamt);
                 self.advance();
             },
             Instruction::Or { rd, rs1, rs2, .. } => {
@@ -311,122 +778,145 @@ impl SoftThread<u64, f64, Dram> {
                 self.registers[rd as usize] = self.registers[rs1 as usize] & self.registers[rs2 as usize];
                 self.advance();
             },
-            Instruction::Fence { .. } => { todo!() }
-            Instruction::ECall => { 
-                // TODO: Call self.ecall() once machine is impl on SoftThread
-                todo!()
+            Instruction::Fence { .. } => {
+                // A single hart observes its own memory operations in
+                // program order, so fences have nothing to order.
+                self.advance();
+            },
+            Instruction::ECall => {
+                // A reset requested from the environment call restarts the
+                // hart, the pc must stay at the reset vector. The signal is
+                // consumed so only a reset from this call counts.
+                self.reset_signal();
+                self.ecall()?;
+                if !self.reset_signal() {
+                    self.advance();
+                }
             },
             Instruction::EBreak => {
-                // TODO: Call ebreak() on debugger once debugger is added into SoftThread
-                todo!()
+                self.ebreak()?;
+                self.advance();
             },
+            Instruction::Sret => self.sret(inst)?,
+            Instruction::Mret => self.mret(inst)?,
+            Instruction::SfenceVma { rs1, rs2 } => self.sfence_vma(rs1, rs2, inst)?,
             Instruction::Lwu { rd, rs1, imm, .. } => {
-                let addr = self.registers[rs1 as usize].wrapping_add((imm as i64) as u64);
-                if let Ok(val) = self.bus.read(&addr.into(), 32) {
-                    self.registers[rd as usize] = self.bus.into_u64(&val);
-                }
+                self.registers[rd as usize] = R::from_u64(self.load(self.addr(rs1, imm), 32)?);
                 self.advance();
             },
             Instruction::Ld { rd, rs1, imm, .. } => {
-                let addr = self.registers[rs1 as usize].wrapping_add((imm as i64) as u64);
-                if let Ok(val) = self.bus.read(&addr.into(), 64) {
-                    self.registers[rd as usize] = self.bus.into_u64(&val);
-                }
+                self.registers[rd as usize] = R::from_u64(self.load(self.addr(rs1, imm), 64)?);
                 self.advance();
             },
             Instruction::Sd { rs1, rs2, imm, .. } => {
-                let addr = self.registers[rs1 as usize].wrapping_add((imm as i64) as u64);
-                let _ = self.bus.write(addr, self.registers[rs2 as usize], 64);
+                self.store(self.addr(rs1, imm), self.registers[rs2 as usize].to_u64(), 64)?;
                 self.advance();
             },
             Instruction::Addiw { rd, rs1, imm, .. } => {
-                self.registers[rd as usize] = ((self.registers[rs1 as usize].wrapping_add(((imm as i64) as u64)) as i32) as i64) as u64;
+                let val = self.registers[rs1 as usize].to_u64().wrapping_add(imm as i64 as u64);
+                self.registers[rd as usize] = sext(i64::from(val as i32));
                 self.advance();
             },
             Instruction::Slliw { rd, rs1, shamt, .. } => {
-                self.registers[rd as usize] = ((self.registers[rs1 as usize].wrapping_shl(shamt) as i32) as i64) as u64;
+                let val = self.registers[rs1 as usize].to_u64().wrapping_shl(shamt);
+                self.registers[rd as usize] = sext(i64::from(val as i32));
+                self.advance();
+            },
+            Instruction::Srliw { rd, rs1, shamt, .. } => {
+                let val = (self.registers[rs1 as usize].to_u64() as u32).wrapping_shr(shamt);
+                self.registers[rd as usize] = sext(i64::from(val as i32));
                 self.advance();
             },
             Instruction::Sraiw { rd, rs1, shamt, .. } => {
-                self.registers[rd as usize] = ((self.registers[rs1 as usize] as i32).wrapping_shr(shamt) as i64) as u64;
+                let val = (self.registers[rs1 as usize].to_u64() as i32).wrapping_shr(shamt);
+                self.registers[rd as usize] = sext(i64::from(val));
                 self.advance();
             },
             Instruction::Addw { rd, rs1, rs2, .. } => {
-                self.registers[rd as usize] = ((self.registers[rs1 as usize].wrapping_add(self.registers[rs2 as usize]) as i32) as i64) as u64;
+                let val = self.registers[rs1 as usize].to_u64().wrapping_add(self.registers[rs2 as usize].to_u64());
+                self.registers[rd as usize] = sext(i64::from(val as i32));
                 self.advance();
             },
             Instruction::Subw { rd, rs1, rs2, .. } => {
-                self.registers[rd as usize] = ((self.registers[rs1 as usize].wrapping_sub(self.registers[rs2 as usize]) as i32) as i64) as u64;
+                let val = self.registers[rs1 as usize].to_u64().wrapping_sub(self.registers[rs2 as usize].to_u64());
+                self.registers[rd as usize] = sext(i64::from(val as i32));
                 self.advance()
             },
             Instruction::Sllw { rd, rs1, rs2, .. } => {
-                let shamt = ((self.registers[rs2 as usize] & 0x3f) as u64) as u32;
-                self.registers[rd as usize] = ((self.registers[rs1 as usize] as u32).wrapping_shl(shamt) as i32) as u64;
+                let val = (self.registers[rs1 as usize].to_u64() as u32).wrapping_shl(self.shamt(rs2));
+                self.registers[rd as usize] = sext(i64::from(val as i32));
                 self.advance();
             },
             Instruction::Srlw { rd, rs1, rs2, .. } => {
-                let shamt = ((self.registers[rs2 as usize] & 0x3f) as u64) as u32;
-                self.registers[rd as usize] = ((self.registers[rs1 as usize] as u32).wrapping_shr(shamt) as i32) as u64;
+                let val = (self.registers[rs1 as usize].to_u64() as u32).wrapping_shr(self.shamt(rs2));
+                self.registers[rd as usize] = sext(i64::from(val as i32));
                 self.advance();
             },
             Instruction::Sraw { rd, rs1, rs2, .. } => {
-                let shamt = ((self.registers[rs2 as usize] & 0x3f) as u64) as u32;
-                self.registers[rd as usize] = ((self.registers[rs1 as usize] as i32) >> (shamt as i32)) as u64;
+                let val = (self.registers[rs1 as usize].to_u64() as i32).wrapping_shr(self.shamt(rs2));
+                self.registers[rd as usize] = sext(i64::from(val));
+                self.advance();
+            },
+            Instruction::FenceI { .. } => {
+                // Instructions are always fetched from the bus, there is
+                // no instruction cache to synchronize.
                 self.advance();
             },
-            Instruction::FenceI { .. } => { todo!() },
+            // csrrw always writes the csr and only skips the read when rd is
+            // x0, the set/clear forms always read and skip the write when
+            // there are no bits to change.
             Instruction::Csrrw { csr, rs1, rd, .. } => {
-                if rd != Register::X0 {
-                    let csr_val = self.csr[csr as usize];
-                    let csr_val = (csr_val as u64).zero_extend(&32);
+                let csr = csr as usize;
+                let csr_val = if rd != Register::X0 { Some(self.read_csr(csr, inst)?) } else { None };
+                self.write_csr(csr, self.registers[rs1 as usize], inst)?;
+                if let Some(csr_val) = csr_val {
                     self.registers[rd as usize] = csr_val;
-                    self.csr[csr as usize] = self.registers[rs1 as usize]
                 }
                 self.advance();
             },
             Instruction::Csrrs { csr, rs1, rd, .. } => {
+                let csr = csr as usize;
+                let csr_val = self.read_csr(csr, inst)?;
                 if rs1 != Register::X0 {
-                    let csr_val = self.csr[csr as usize];
-                    let csr_val = (csr_val as u64).zero_extend(&32);
-                    self.registers[rd as usize] = csr_val;
-                    self.csr[csr as usize] = self.csr[csr as usize] | self.registers[rs1 as usize];    
+                    self.write_csr(csr, csr_val | self.registers[rs1 as usize], inst)?;
                 }
+                self.registers[rd as usize] = csr_val;
                 self.advance();
             },
             Instruction::Csrrc { csr, rs1, rd, .. } => {
+                let csr = csr as usize;
+                let csr_val = self.read_csr(csr, inst)?;
                 if rs1 != Register::X0 {
-                    let csr_val = self.csr[csr as usize];
-                    let csr_val = (csr_val as u64).zero_extend(&32);
-                    self.registers[rd as usize] = csr_val;
-                    self.csr[csr as usize] = self.csr[csr as usize] & self.registers[rs1 as usize];
+                    self.write_csr(csr, csr_val & !self.registers[rs1 as usize], inst)?;
                 }
+                self.registers[rd as usize] = csr_val;
                 self.advance();
             },
             Instruction::Csrrwi { rd, csr, uimm, .. } => {
-                if rd != Register::X0 {
-                    let csr_val = self.csr[csr as usize];
-                    let imm = (uimm as u64).zero_extend(&32);
+                let csr = csr as usize;
+                let csr_val = if rd != Register::X0 { Some(self.read_csr(csr, inst)?) } else { None };
+                self.write_csr(csr, R::from_u64(u64::from(uimm)), inst)?;
+                if let Some(csr_val) = csr_val {
                     self.registers[rd as usize] = csr_val;
-                    self.csr[csr as usize] = imm;
                 }
                 self.advance();
             },
             Instruction::Csrrsi { rd, csr, uimm, .. } => {
-                if uimm != Register::X0 as u32 {
-                    let csr_val = self.csr[csr as usize];
-                    let imm = (uimm as u64).zero_extend(&32);
-                    self.registers[rd as usize] = csr_val;
-                    self.csr[csr as usize] = self.csr[csr as usize] | imm;
+                let csr = csr as usize;
+                let csr_val = self.read_csr(csr, inst)?;
+                if uimm != 0 {
+                    self.write_csr(csr, csr_val | R::from_u64(u64::from(uimm)), inst)?;
                 }
+                self.registers[rd as usize] = csr_val;
                 self.advance();
             },
             Instruction::Csrrci { rd, csr, uimm, .. } => {
-                if uimm != Register::X0 as u32 {
-                    let csr_val = self.csr[csr as usize];
-                    let imm = (uimm as u64).zero_extend(&32);
-                    self.registers[rd as usize] = csr_val;
-                    self.csr[csr as usize] = self.csr[csr as usize] & imm;
+                let csr = csr as usize;
+                let csr_val = self.read_csr(csr, inst)?;
+                if uimm != 0 {
+                    self.write_csr(csr, csr_val & !R::from_u64(u64::from(uimm)), inst)?;
                 }
+                self.registers[rd as usize] = csr_val;
                 self.advance();
             },
             Instruction::Mul { rd, rs1, rs2, .. } => {
@@ -461,1028 +951,692 @@ impl SoftThread<u64, f64, Dram> {
                 self.registers[rd as usize] = self.registers[rs1 as usize].oflow_rem(&self.registers[rs2 as usize]);
                 self.advance();
             },
+            // The W forms work on the low words and sign extend the result.
             Instruction::Mulw { rd, rs1, rs2, .. } => {
-                self.registers[rd as usize] = self.registers[rs1 as usize].oflow_mul(&self.registers[rs2 as usize]);
+                let (a, b) = self.words(rs1, rs2);
+                self.registers[rd as usize] = sext(i64::from(a.oflow_mul(&b) as i32));
                 self.advance();
             },
             Instruction::Divw { rd, rs1, rs2, .. } => {
-                self.registers[rd as usize] = self.registers[rs1 as usize].oflow_div_signed(&self.registers[rs2 as usize]);
+                let (a, b) = self.words(rs1, rs2);
+                self.registers[rd as usize] = sext(i64::from(a.oflow_div_signed(&b) as i32));
                 self.advance();
             },
             Instruction::Divuw { rd, rs1, rs2, .. } => {
-                self.registers[rd as usize] = self.registers[rs1 as usize].oflow_div(&self.registers[rs2 as usize]);
+                let (a, b) = self.words(rs1, rs2);
+                self.registers[rd as usize] = sext(i64::from(a.oflow_div(&b) as i32));
                 self.advance();
             },
             Instruction::Remw { rd, rs1, rs2, .. } => {
-                self.registers[rd as usize] = self.registers[rs1 as usize].oflow_rem_signed(&self.registers[rs2 as usize]);
+                let (a, b) = self.words(rs1, rs2);
+                self.registers[rd as usize] = sext(i64::from(a.oflow_rem_signed(&b) as i32));
                 self.advance();
             },
             Instruction::RemuW { rd, rs1, rs2, .. } => {
-                self.registers[rd as usize] = self.registers[rs1 as usize].oflow_rem(&self.registers[rs2 as usize]);
-                self.advance();
+                let (a, b) = self.words(rs1, rs2);
+                self.registers[rd as usize] = sext(i64::from(a.oflow_rem(&b) as i32));
+                self.advance();
+            },
+            // Words must be naturally aligned to 32 bits and doublewords
+            // to 64 bits.
+            Instruction::LrW { rd, rs1, .. } => self.load_reserved(rd, rs1, 32)?,
+            Instruction::LrD { rd, rs1, .. } => self.load_reserved(rd, rs1, 64)?,
+            Instruction::ScW { rd, rs1, rs2, .. } => self.store_conditional(rd, rs1, rs2, 32)?,
+            Instruction::ScD { rd, rs1, rs2, .. } => self.store_conditional(rd, rs1, rs2, 64)?,
+            Instruction::AmoswapW { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, 32, |_, src| src)?,
+            Instruction::AmoaddW { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, 32, u64::wrapping_add)?,
+            Instruction::AmoxorW { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, 32, |mem, src| mem ^ src)?,
+            Instruction::AmoandW { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, 32, |mem, src| mem & src)?,
+            Instruction::AmoorW { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, 32, |mem, src| mem | src)?,
+            Instruction::AmominW { rd, rs1, rs2, .. } => {
+                self.amo(rd, rs1, rs2, 32, |mem, src| std::cmp::min(mem as i32, src as i32) as u64)?
             },
-            // For W instructions below ALL words being read from
-            // memory must be naturally aligned to 32 bits
-            // i.e. mod 4 == 0;
-            // For D instructions belwo ALL doublewords being
-            // read from memory most be naturally aligned to
-            // 64 bit words, i.e. mod 8 == 0;
-            Instruction::LrW { rd, rs1, .. } => {
-
-                // Test whether vec or hashset is best suited for this.
-                let addr = self.registers[rs1 as usize];
-
-                if addr % 4 != 0 {
-                    // TODO: Reject
-                    todo!();
-                }
-                
-                let res = self.bus.read(&addr, 32);
-                if let Ok(val) = res {
-                    let val = ((val as i32) as i64) as u64;
-                    self.registers[rd as usize] = val;
-                    self.res.push(self.registers[rs1 as usize]);
-                }
-
-                self.advance();
+            Instruction::AmomaxW { rd, rs1, rs2, .. } => {
+                self.amo(rd, rs1, rs2, 32, |mem, src| std::cmp::max(mem as i32, src as i32) as u64)?
             },
-            Instruction::ScW { rd, rs1, rs2, .. } => {
-                // if an address reservation is still value
-                // and contains the bytes being written
-                // then write the word in rs2 to addr in
-                // rs1, and set rd to zero.
-                // otherwise write a nonzero value to rd.
-                // Invalidate any reservation held be this
-                // thread.
-                let addr = self.registers[rs1 as usize];
-                
-                if addr % 4 != 0 {
-                    //TODO: Reject
-                    todo!();
-                }
-                    
-                if self.res.contains(&addr) {
-                    self.res.retain(|x| *x != addr);
-                    let word = self.registers[rs2 as usize];
-                    self.bus.write(addr, word, 32);
-                    self.registers[rd as usize] = 0;
-                } else {
-                    self.res.retain(|x| *x != addr);
-                    self.registers[rd as usize] = 1;
-                }
-                self.advance();
+            Instruction::AmominuW { rd, rs1, rs2, .. } => {
+                self.amo(rd, rs1, rs2, 32, |mem, src| u64::from(std::cmp::min(mem as u32, src as u32)))?
             },
-            Instruction::AmoswapW { rd, rs1, rs2, ..} => {
-                // read a word from the address in rs1
-                // write the value in rs2 register to
-                // address in rs1, take value from rs1 and
-                // sign extend then store in rd
-                let addr = self.registers[rs1 as usize];
-
-                if addr % 4 != 0 {
-                    // Reject
-                    todo!();
-                }
-                
-                if let Ok(temp) = self.bus.read(&addr, 32) {
-                    let temp = ((temp as i32) as i64) as u64;
-                    let val = self.registers[rs2 as usize];                    
-                    let _ = self.bus.write(addr, val, 32);
-                    self.registers[rd as usize] = temp;  
-
-                }
-                
-                self.advance();
-            },
-            Instruction::AmoaddW { rd, rs1, rs2, ..} => {
-                // read word from address in rs1
-                // add the value from rs2 to the word
-                // read at rs1 address and save result
-                // in memory at address in rs1. Write
-                // previous value in address at rs1
-                // to rd.
-                let addr = self.registers[rs1 as usize];
-                
-                if addr % 4 != 0 {
-                    // Reject
-                    todo!();
-                }
-                
-                if let Ok(temp) = self.bus.read(&addr, 32) {
-                    let temp = ((temp as i32) as i64) as u64;
-                    let val = self.registers[rs2 as usize];
-                    let res = temp + val;
-                    let _ = self.bus.write(addr, res, 32);
-                    self.registers[rd as usize] = temp; 
-                }
-                self.advance();
+            Instruction::AmomaxuW { rd, rs1, rs2, .. } => {
+                self.amo(rd, rs1, rs2, 32, |mem, src| u64::from(std::cmp::max(mem as u32, src as u32)))?
             },
-            Instruction::AmoxorW { rd, rs1, rs2, .. } => {
-                // read word from address in rs1
-                // xor the word against the value in rs2
-                // save the original value found at address
-                // in rs1 to rd. Save the xor value in the
-                // memory at the address from rs1.
-                let addr = self.registers[rs1 as usize];
-                
-                if addr % 4 != 0 {
-                    // Reject
-                    todo!();
-                }
-                
-                if let Ok(temp) = self.bus.read(&addr, 32) {
-                    let temp = ((temp as i32) as i64) as u64;
-                    let val = self.registers[rs2 as usize];
-                    let res = temp ^ val;
-                    let _ = self.bus.write(addr, res, 32);
-                    self.registers[rd as usize] = temp;
-                }
-
-                self.advance();
+            Instruction::AmoswapD { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, 64, |_, src| src)?,
+            Instruction::AmoaddD { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, 64, u64::wrapping_add)?,
+            Instruction::AmoxorD { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, 64, |mem, src| mem ^ src)?,
+            Instruction::AmoandD { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, 64, |mem, src| mem & src)?,
+            Instruction::AmoorD { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, 64, |mem, src| mem | src)?,
+            Instruction::AmominD { rd, rs1, rs2, .. } => {
+                self.amo(rd, rs1, rs2, 64, |mem, src| std::cmp::min(mem as i64, src as i64) as u64)?
             },
-            Instruction::AmoandW { rd, rs1, rs2, .. } => {
-                // read word from address in rs1
-                // bitwise and word against the value in rs2
-                // save the original value found at address
-                // in rs1 to rd. Save the bitwise and'd value
-                // in the memory at the address from rs1.
-                let addr = self.registers[rs1 as usize];
-                
-                if addr % 4 != 0 {
-                    // Reject
-                    todo!();
-                }
-                
-                if let Ok(temp) = self.bus.read(&addr, 32) {
-                    let temp = ((temp as i32) as i64) as u64;     
-                    let val = self.registers[rs2 as usize];
-                    let res = temp & val;
-                    let _ = self.bus.write(addr, res, 32);
-                    self.registers[rd as usize] = temp;
-                }
-
-                self.advance();
+            Instruction::AmomaxD { rd, rs1, rs2, .. } => {
+                self.amo(rd, rs1, rs2, 64, |mem, src| std::cmp::max(mem as i64, src as i64) as u64)?
+            },
+            Instruction::AmominuD { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, 64, std::cmp::min)?,
+            Instruction::AmomaxuD { rd, rs1, rs2, .. } => self.amo(rd, rs1, rs2, 64, std::cmp::max)?,
+            // Single precision loads, stores and moves go through f32, the
+            // register holds the value widened rather than the raw bits. The
+            // arithmetic below works on the float registers at their own
+            // width and is shared by the S, D and Q forms.
+            Instruction::Flw { rd, rs1, imm, .. } => {
+                let bits = self.load(self.addr(rs1, imm), 32)?;
+                self.write_freg(rd, F::from_f32(f32::from_bits(bits as u32)));
             },
-            Instruction::AmoorW { rd, rs1, rs2, .. } => {
-                // read word from address in rs1
-                // bitwise or word against value in rs2
-                // save the original value found at address
-                // in rs1 to rd. save the bitwise or'd value
-                // in the memory at the address from rs1.
-                let addr = self.registers[rs1 as usize];
-
-                if addr % 4 != 0 {
-                    // Reject
-                    todo!();
-                }
-                
-                if let Ok(temp) = self.bus.read(&addr, 32) {
-                    let temp = ((temp as i32) as i64) as u64;
-                    let val = self.registers[rs2 as usize];
-                    let res = temp | val;
-                    let _ = self.bus.write(addr, res, 32);
-                    self.registers[rd as usize] = temp;
-                }
-                self.advance();
+            Instruction::Fsw { rs1, rs2, imm, .. } => {
+                let bits = self.f_registers[rs2 as usize].to_f32().to_bits();
+                self.store(self.addr(rs1, imm), u64::from(bits), 32)?;
+                self.advance();
+            },
+            Instruction::Fld { rd, rs1, imm, .. } | Instruction::Flq { rd, rs1, imm, .. } => {
+                let bits = self.load(self.addr(rs1, imm), 64)?;
+                self.write_freg(rd, F::from_f64(f64::from_bits(bits)));
+            },
+            Instruction::Fsd { rs1, rs2, imm, .. } | Instruction::Fsq { rs1, rs2, imm, .. } => {
+                let bits = self.f_registers[rs2 as usize].to_f64().to_bits();
+                self.store(self.addr(rs1, imm), bits, 64)?;
+                self.advance();
+            },
+            Instruction::FmaddS { rd, rs1, rs2, rs3, .. }
+            | Instruction::FmaddD { rd, rs1, rs2, rs3, .. }
+            | Instruction::FmaddQ { rd, rs1, rs2, rs3, .. } => {
+                let [a, b, c] = [rs1, rs2, rs3].map(|r| self.f_registers[r as usize]);
+                self.write_freg(rd, a.mul_add(b, c));
+            },
+            Instruction::FmsubS { rd, rs1, rs2, rs3, .. }
+            | Instruction::FmsubD { rd, rs1, rs2, rs3, .. }
+            | Instruction::FmsubQ { rd, rs1, rs2, rs3, .. } => {
+                let [a, b, c] = [rs1, rs2, rs3].map(|r| self.f_registers[r as usize]);
+                self.write_freg(rd, a.mul_add(b, -c));
+            },
+            Instruction::FnmsubS { rd, rs1, rs2, rs3, .. }
+            | Instruction::FnmsubD { rd, rs1, rs2, rs3, .. }
+            | Instruction::FnmsubQ { rd, rs1, rs2, rs3, .. } => {
+                let [a, b, c] = [rs1, rs2, rs3].map(|r| self.f_registers[r as usize]);
+                self.write_freg(rd, (-a).mul_add(b, c));
+            },
+            Instruction::FnmaddS { rd, rs1, rs2, rs3, .. }
+            | Instruction::FnmaddD { rd, rs1, rs2, rs3, .. }
+            | Instruction::FnmaddQ { rd, rs1, rs2, rs3, .. } => {
+                let [a, b, c] = [rs1, rs2, rs3].map(|r| self.f_registers[r as usize]);
+                self.write_freg(rd, (-a).mul_add(b, -c));
+            },
+            Instruction::FaddS { rd, rs1, rs2, .. }
+            | Instruction::FaddD { rd, rs1, rs2, .. }
+            | Instruction::FaddQ { rd, rs1, rs2, .. } => {
+                self.write_freg(rd, self.f_registers[rs1 as usize] + self.f_registers[rs2 as usize]);
+            },
+            Instruction::FsubS { rd, rs1, rs2, .. }
+            | Instruction::FsubD { rd, rs1, rs2, .. }
+            | Instruction::FsubQ { rd, rs1, rs2, .. } => {
+                self.write_freg(rd, self.f_registers[rs1 as usize] - self.f_registers[rs2 as usize]);
+            },
+            Instruction::FmulS { rd, rs1, rs2, .. }
+            | Instruction::FmulD { rd, rs1, rs2, .. }
+            | Instruction::FmulQ { rd, rs1, rs2, .. } => {
+                self.write_freg(rd, self.f_registers[rs1 as usize] * self.f_registers[rs2 as usize]);
+            },
+            Instruction::FdivS { rd, rs1, rs2, .. }
+            | Instruction::FdivD { rd, rs1, rs2, .. }
+            | Instruction::FdivQ { rd, rs1, rs2, .. } => {
+                self.write_freg(rd, self.f_registers[rs1 as usize] / self.f_registers[rs2 as usize]);
+            },
+            Instruction::FsqrtS { rd, rs1, .. }
+            | Instruction::FsqrtD { rd, rs1, .. }
+            | Instruction::FsqrtQ { rd, rs1, .. } => {
+                self.write_freg(rd, self.f_registers[rs1 as usize].sqrt());
+            },
+            Instruction::FsgnjS { rd, rs1, rs2, .. }
+            | Instruction::FsgnjD { rd, rs1, rs2, .. }
+            | Instruction::FsgnjQ { rd, rs1, rs2, .. } => {
+                self.write_freg(rd, self.f_registers[rs1 as usize].copysign(self.f_registers[rs2 as usize]));
+            },
+            Instruction::FsgnjnS { rd, rs1, rs2, .. }
+            | Instruction::FsgnjnD { rd, rs1, rs2, .. }
+            | Instruction::FsgnjnQ { rd, rs1, rs2, .. } => {
+                self.write_freg(rd, self.f_registers[rs1 as usize].copysign(-self.f_registers[rs2 as usize]));
             },
-            Instruction::AmominW { rd, rs1, rs2, .. } => {
-                // read word from address in rs1
-                // compare the value of the word to the
-                // value in rs2 and save the lowest value
-                // to memory at the address in rs1.
-                // store the original word at address in rs1
-                // to rd. 
-                let addr = self.registers[rs1 as usize];
-                
-                if addr % 4 != 0 {
-                    // Reject
-                    todo!();
-                }
-                
-                if let Ok(temp) = self.bus.read(&addr, 32) {
-                    let temp = ((temp as i32) as i64) as u64;
-                    let val = self.registers[rs2 as usize];
-                    let res = std::cmp::min(temp, val);   
-                    let _ = self.bus.write(addr, res, 32);
-                    self.registers[rd as usize] = temp;
-                }
-                self.advance();
+            Instruction::FsgnjxS { rd, rs1, rs2, .. } => {
+                let sign = self.f_registers[rs2 as usize].to_f32().to_bits() & 0x8000_0000;
+                let bits = self.f_registers[rs1 as usize].to_f32().to_bits() ^ sign;
+                self.write_freg(rd, F::from_f32(f32::from_bits(bits)));
             },
-            Instruction::AmomaxW { rd, rs1, rs2, .. } => {
-                // read word from address in rs1
-                // compare the value of the word to the
-                // value in rs2. Store the highest value
-                // in memory at the address in rs1.
-                // store the original word atw address in rs1
-                // to rd.
-                let addr = self.registers[rs1 as usize];
-                
-                if addr % 4 != 0 {
-                    // Reject
-                    todo!();
-                }
-                
-                if let Ok(temp) = self.bus.read(&addr, 32) {
-                    let temp = ((temp as i32) as i64) as u64;
-                    let val = self.registers[rs2 as usize];
-                    let res = std::cmp::max(temp, val);
-                    let _ = self.bus.write(addr, res, 32);
-                    self.registers[rd as usize] = temp;
-                }
-                self.advance();
+            Instruction::FsgnjxD { rd, rs1, rs2, .. } | Instruction::FsgnjxQ { rd, rs1, rs2, .. } => {
+                let sign = self.f_registers[rs2 as usize].to_f64().to_bits() & 0x8000_0000_0000_0000;
+                let bits = self.f_registers[rs1 as usize].to_f64().to_bits() ^ sign;
+                self.write_freg(rd, F::from_f64(f64::from_bits(bits)));
             },
-            Instruction::AmominuW { rd, rs1, rs2, .. } => {
-                // read word from address in rs1
-                // compare the unsigned value to an unsigned
-                // value in rs2. Store the lowest value to
-                // memory at the address in rs1
-                // store the original word at address in rs1
-                // to rd.
-                let addr = self.registers[rs1 as usize];
-                
-                if addr % 4 != 0 {
-                    // Reject
-                    todo!();
-                }
-
-                if let Ok(temp) = self.bus.read(&addr, 32) {
-                    let temp = temp;
-                    let val = self.registers[rs2 as usize];
-                    let res = std::cmp::min(temp, val);
-                    let _ = self.bus.write(addr, res, 32);
-                    self.registers[rd as usize] = temp;
-                }
-
-                self.advance();
+            Instruction::FminS { rd, rs1, rs2, .. }
+            | Instruction::FminD { rd, rs1, rs2, .. }
+            | Instruction::FminQ { rd, rs1, rs2, .. } => {
+                self.write_freg(rd, self.f_registers[rs1 as usize].min(self.f_registers[rs2 as usize]));
             },
-            Instruction::AmomaxuW { rd, rs1, rs2, .. } => {
-                // read word from address in rs1
-                // compare the unsigned value to an unsigned
-                // value in rs2. Store the higheste value to
-                // memory at the address in rs1
-                // store the original word at address in rs1
-                // to rd.
-                let addr = self.registers[rs1 as usize];
-                
-                if addr % 4 != 0 {
-                    // Reject
-                    todo!();
-                }
-
-                if let Ok(temp) = self.bus.read(&addr, 32) {
-                    let val = self.registers[rs2 as usize];
-                    let res = std::cmp::max(temp, val);
-                    let _ = self.bus.write(addr, res, 32);
-                    self.registers[rd as usize] = temp;
-                }
+            Instruction::FmaxS { rd, rs1, rs2, .. }
+            | Instruction::FmaxD { rd, rs1, rs2, .. }
+            | Instruction::FmaxQ { rd, rs1, rs2, .. } => {
+                self.write_freg(rd, self.f_registers[rs1 as usize].max(self.f_registers[rs2 as usize]));
+            },
+            Instruction::FeqS { rd, rs1, rs2, .. }
+            | Instruction::FeqD { rd, rs1, rs2, .. }
+            | Instruction::FeqQ { rd, rs1, rs2, .. } => {
+                let equal = self.f_registers[rs1 as usize] == self.f_registers[rs2 as usize];
+                self.registers[rd as usize] = R::from_u64(u64::from(equal));
                 self.advance();
             },
-            Instruction::LrD { rd, rs1, .. } => {
-                // See LrD, but instead of reading word
-                // from address at rs1, read double word.
-                let addr = self.registers[rs1 as usize];
-                
-                if addr % 8 != 0 {
-                    // TODO: Reject
-                    todo!();
-                }
-
-                if let Ok(temp) = self.bus.read(&addr, 64) {
-                    let val = (temp as i64) as u64;    
-                    self.registers[rd as usize] = val;
-                    self.res.push(self.registers[rs1 as usize]);
-                } 
-                self.advance();
-            },
-            Instruction::ScD { rd, rs1, rs2, .. } => {
-                // See ScW, but instead of conditionally
-                // saving a word, save a double word.
-                let addr = self.registers[rs1 as usize];
-                
-                if addr % 8 != 0 {
-                    //TODO: Reject
-                    todo!();
-                }
-                    
-                if self.res.contains(&addr) {
-                    self.res.retain(|x| *x != addr);
-                    let dword = self.registers[rs2 as usize];
-                    let _ = self.bus.write(addr, dword, 64);
-                    self.registers[rd as usize] = 0;
-                } else {
-                    self.res.retain(|x| *x != addr);
-                    self.registers[rd as usize] = 1;
-                }
-
+            Instruction::FltS { rd, rs1, rs2, .. }
+            | Instruction::FltD { rd, rs1, rs2, .. }
+            | Instruction::FltQ { rd, rs1, rs2, .. } => {
+                let less = self.f_registers[rs1 as usize] < self.f_registers[rs2 as usize];
+                self.registers[rd as usize] = R::from_u64(u64::from(less));
                 self.advance();
             },
-            Instruction::AmoswapD { rd, rs1, rs2, ..} => {
-                // read a doubleword from the address in rs1
-                // write the value in rs2 register to
-                // address in rs1, take value from rs1 and
-                // sign extend then store in rd
-                let addr = self.registers[rs1 as usize];
-
-                if addr % 8 != 0 {
-                    // Reject
-                    todo!();
-                }
-                
-                if let Ok(temp) = self.bus.read(&addr, 64) {
-                    let temp: u64 = (temp as i64) as u64;
-                    let val = self.registers[rs2 as usize];
-                    let _ = self.bus.write(addr, val, 64);
-                    self.registers[rd as usize] = temp;
-                }
+            Instruction::FleS { rd, rs1, rs2, .. }
+            | Instruction::FleD { rd, rs1, rs2, .. }
+            | Instruction::FleQ { rd, rs1, rs2, .. } => {
+                let less_equal = self.f_registers[rs1 as usize] <= self.f_registers[rs2 as usize];
+                self.registers[rd as usize] = R::from_u64(u64::from(less_equal));
                 self.advance();
             },
-            Instruction::AmoaddD { rd, rs1, rs2, ..} => {
-                // read doubleword from address in rs1
-                // add the value from rs2 to the doubleword
-                // read at rs1 address and save result
-                // in memory at address in rs1. Write
-                // previous value in address at rs1
-                // to rd.
-                let addr = self.registers[rs1 as usize];
-                
-                if addr % 8 != 0 {
-                    // Reject
-                    todo!();
-                }
-
-                if let Ok(temp) = self.bus.read(&addr, 64) {
-                    let temp = (temp as i64) as u64;
-                    let val = self.registers[rs2 as usize];
-                    let res = temp + val;
-                    let _ = self.bus.write(addr, res, 64);
-                    self.registers[rd as usize] = temp;
-                }
+            Instruction::FclassS { rd, rs1, .. } => {
+                let val = self.f_registers[rs1 as usize].to_f32();
+                let class = fclass(val.classify(), val.is_sign_negative(), val.to_bits() & 0x40_0000 != 0);
+                self.registers[rd as usize] = R::from_u64(u64::from(class));
                 self.advance();
             },
-            Instruction::AmoxorD { rd, rs1, rs2, .. } => {
-                // read doubleword from address in rs1
-                // xor the doubleword against the value in rs2
-                // save the original value found at address
-                // in rs1 to rd. Save the xor value in the
-                // memory at the address from rs1.
-                let addr = self.registers[rs1 as usize];
-                
-                if addr % 8 != 0 {
-                    // Reject
-                    todo!();
-                }
-
-                if let Ok(temp) = self.bus.read(&addr, 64) {
-                    let temp = (temp as i64) as u64;
-                    let val = self.registers[rs2 as usize];
-                    let res = temp ^ val;
-                    let _ = self.bus.write(addr, res, 64);
-                    self.registers[rd as usize] = temp;
-                }
+            Instruction::FclassD { rd, rs1, .. } | Instruction::FclassQ { rd, rs1, .. } => {
+                let val = self.f_registers[rs1 as usize].to_f64();
+                let class = fclass(val.classify(), val.is_sign_negative(), val.to_bits() & 0x8_0000_0000_0000 != 0);
+                self.registers[rd as usize] = R::from_u64(u64::from(class));
                 self.advance();
             },
-            Instruction::AmoandD { rd, rs1, rs2, .. } => {
-                // read doubleword from address in rs1
-                // bitwise and doubleword against the value in rs2
-                // save the original value found at address
-                // in rs1 to rd. Save the bitwise and'd value
-                // in the memory at the address from rs1.
-                let addr = self.registers[rs1 as usize];
-                
-                if addr % 8 != 0 {
-                    // Reject
-                    todo!();
-                }
-                
-                if let Ok(temp) = self.bus.read(&addr, 64) {
-                    let temp = (temp as i64) as u64;
-                    let val = self.registers[rs2 as usize];
-                    let res = temp & val;
-                    let _ = self.bus.write(addr, res, 64);
-                    self.registers[rd as usize] = temp;
-                }
+            Instruction::FcvtWS { rd, rs1, rm, .. }
+            | Instruction::FcvtWD { rd, rs1, rm, .. }
+            | Instruction::FcvtWQ { rd, rs1, rm, .. } => {
+                let val = float_to_i32(self.f_registers[rs1 as usize].to_f64(), self.rounding_mode(rm));
+                self.registers[rd as usize] = sext(i64::from(val as i32));
                 self.advance();
             },
-            Instruction::AmoorD { rd, rs1, rs2, .. } => {
-                // read doubleword from address in rs1
-                // bitwise or doubleword against value in rs2
-                // save the original value found at address
-                // in rs1 to rd. save the bitwise or'd value
-                // in the memory at the address from rs1.
-                let addr = self.registers[rs1 as usize];
-
-                if addr % 8 != 0 {
-                    // Reject
-                    todo!();
-                }
-                
-                if let Ok(temp) = self.bus.read(&addr, 64) {
-                    let temp = (temp as i64) as u64;
-                    let val = self.registers[rs2 as usize];
-                    let res = temp | val;
-                    let _ = self.bus.write(addr, res, 64);
-                    self.registers[rd as usize] = temp;                    
-                }
+            Instruction::FcvtWUS { rd, rs1, rm, .. }
+            | Instruction::FcvtWUD { rd, rs1, rm, .. }
+            | Instruction::FcvtWUQ { rd, rs1, rm, .. } => {
+                let val = float_to_u32(self.f_registers[rs1 as usize].to_f64(), self.rounding_mode(rm));
+                self.registers[rd as usize] = sext(i64::from(val as i32));
                 self.advance();
             },
-            Instruction::AmominD { rd, rs1, rs2, .. } => {
-                // read doubleword from address in rs1
-                // compare the value of the doubleword to the
-                // value in rs2 and save the lowest value
-                // to memory at the address in rs1.
-                // store the original doubleword at address in rs1
-                // to rd. 
-                let addr = self.registers[rs1 as usize];
-                
-                if addr % 8 != 0 {
-                    // Reject
-                    todo!();
-                }
-                
-                let res = self.bus.read(&addr, 64);
-                if let Ok(temp) = res {
-                    let temp = (temp as i64) as u64;
-                    let val = self.registers[rs2 as usize];
-                    let fin = std::cmp::min(temp, val);
-                    let _ = self.bus.write(addr, fin, 64);
-                    self.registers[rd as usize] = temp;
-                }
-                self.advance();
+            Instruction::FcvtSW { rd, rs1, .. } => {
+                let val = self.registers[rs1 as usize].to_u64() as i32;
+                self.write_freg(rd, F::from_f32(val as f32));
             },
-            Instruction::AmomaxD { rd, rs1, rs2, .. } => {
-                let addr = self.registers[rs1 as usize];
-                
-                if addr % 4 != 0 {
-                    // Reject
-                    todo!();
-                }
-                
-                let res = self.bus.read(&addr, 64);
-                if let Ok(temp) = res {
-                    let temp = (temp as i64) as u64;
-                    let val = self.registers[rs2 as usize];
-                    let fin = std::cmp::max(temp, val);
-                    let _ = self.bus.write(addr, fin, 64);
-                    self.registers[rd as usize] = temp;
-                }
-                self.advance();
+            Instruction::FcvtSWU { rd, rs1, .. } => {
+                let val = self.registers[rs1 as usize].to_u64() as u32;
+                self.write_freg(rd, F::from_f32(val as f32));
             },
-            Instruction::AmominuD { rd, rs1, rs2, .. } => {
-                // read doubleword from address in rs1
-                // compare the unsigned value to an unsigned
-                // value in rs2. Store the lowest value to
-                // memory at the address in rs1
-                // store the original doubleword at address in rs1
-                // to rd.
-                let addr = self.registers[rs1 as usize];
-                
-                if addr % 8 != 0 {
-                    // Reject
-                    todo!();
-                }
-                
-                let res = self.bus.read(&addr, 64);
-                if let Ok(temp) = res {
-                    let val = self.registers[rs2 as usize];
-                    let fin = std::cmp::min(temp, val);
-                    let _ = self.bus.write(addr, fin, 64);
-                    self.registers[rd as usize] = temp;
-                }
-                self.advance();
+            Instruction::FcvtDW { rd, rs1, .. } | Instruction::FcvtQW { rd, rs1, .. } => {
+                let val = self.registers[rs1 as usize].to_u64() as i32;
+                self.write_freg(rd, F::from_f64(f64::from(val)));
             },
-            Instruction::AmomaxuD { rd, rs1, rs2, .. } => {
-                // read doubleword from address in rs1
-                // compare the unsigned value to an unsigned
-                // value in rs2. Store the higheste value to
-                // memory at the address in rs1
-                // store the original doubleword at address in rs1
-                // to rd.
-                let addr = self.registers[rs1 as usize];
-                
-                if addr % 4 != 0 {
-                    // Reject
-                    todo!();
-                }
-                
-                if let Ok(temp) = self.bus.read(&addr, 64) {
-                    let val = self.registers[rs2 as usize];
-                    let fin = std::cmp::max(temp, val);
-                    let _ = self.bus.write(addr, fin, 64);
-                    self.registers[rd as usize] = temp;
-                }
-                self.advance();
-            },
-            Instruction::Flw { rd, rs1, imm, .. } => {
-                let addr = self.registers[rs1 as usize].wrapping_add((imm as u32) as u64);
-                if let Ok(bits) = self.bus.read(&addr, 32) {
-                    let val = f32::from_bits((bits as u32));
-                    self.f_registers[rd as usize] = val as f64;
-                }
-                self.advance();
-            },
-            Instruction::Fsw { rs1, rs2, imm, .. } => {
-                // store value in f_register rs2 as bits into memory at address in rs1 + imm
-                let addr = self.registers[rs1 as usize].wrapping_add((imm as u32) as u64);
-                let val = (self.f_registers[rs2 as usize] as f32).to_bits() as u64;
-                let _ = self.bus.write(addr, val, 32);
-                self.advance();
-            },
-            Instruction::FmaddS { rd, rs1, rs2, rs3, rm, .. } => {
-                // multiply value in f_register[rs1] by value in f_register[rs2]
-                // add value in rs3
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                let rs3_val = self.f_registers[rs3 as usize];
-                self.f_registers[rd as usize] = rs1_val.mul_add(rs2_val, rs3_val);
-                self.advance();
-            },
-            Instruction::FmsubS { rd, rs1, rs2, rs3, rm, .. } => {
-                // multiply value in f_register[rs1] by value in f_register[rs2]
-                // subtract value in rs3
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                let rs3_val = -self.f_registers[rs3 as usize];
-                self.f_registers[rd as usize] = rs1_val.mul_add(rs2_val, rs3_val);
-                self.advance();
-            },
-            Instruction::FnmsubS { rd, rs1, rs2, rs3, rm, .. } => {
-                let rs1_val = -self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                let rs3_val = -self.f_registers[rs3 as usize];
-                self.f_registers[rd as usize] = rs1_val.mul_add(rs2_val, rs3_val);
-                self.advance();
-            },
-            Instruction::FnmaddS { rd, rs1, rs2, rs3, rm, .. } => {
-                let rs1_val = -self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                let rs3_val = self.f_registers[rs3 as usize];
-                self.f_registers[rd as usize] = rs1_val.mul_add(rs2_val, rs3_val);
-                self.advance();
-            },
-            Instruction::FaddS { rd, rs1, rs2, rm, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.f_registers[rd as usize] = rs1_val + rs2_val;
-                self.advance();
-            },
-            Instruction::FsubS { rd, rs1, rs2, rm, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.f_registers[rd as usize] = rs1_val - rs2_val;
-                self.advance();
-            },
-            Instruction::FmulS { rd, rs1, rs2, rm, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.f_registers[rd as usize] = rs1_val * rs2_val;
-                self.advance();
-            },
-            Instruction::FdivS { rd, rs1, rs2, rm, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.f_registers[rd as usize] = rs1_val / rs2_val;
-                self.advance();
-            },
-            Instruction::FsqrtS { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = (self.f_registers[rs1 as usize].sqrt());
-                self.advance();
-            },
-            Instruction::FsgnjS { rd, rs1, rs2, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.f_registers[rd as usize] = rs1_val.copysign(rs2_val);
-                self.advance();
-            },
-            Instruction::FsgnjnS { rd, rs1, rs2, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = -self.f_registers[rs2 as usize];
-                self.f_registers[rd as usize] = rs1_val.copysign(rs2_val);
-                self.advance();
-            },
-            Instruction::FsgnjxS { rd, rs1, rs2, .. } => {
-                let sign_1 = (self.f_registers[rs1 as usize] as f32).to_bits() & 0x8000_0000;
-                let sign_2 = (self.f_registers[rs2 as usize] as f32).to_bits() & 0x8000_0000;
-                let other = (self.f_registers[rs1 as usize] as f32).to_bits() & 0x7fff_ffff;
-                self.f_registers[rd as usize] = (f32::from_bits((sign_1 ^ sign_2) | other)) as f64;
-                self.advance();
-            },
-            Instruction::FminS { rd, rs1, rs2, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.f_registers[rd as usize] = rs1_val.min(rs2_val);
-                self.advance();
-            },
-            Instruction::FmaxS { rd, rs1, rs2, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.f_registers[rd as usize] = rs1_val.max(rs2_val);
-                self.advance();
-            },
-            Instruction::FcvtWS { rd, rs1, rm, .. } => {
-                self.registers[rd as usize] = (self.f_registers[rs1 as usize].round() as i32) as u64;
-                self.advance();
-            },
-            Instruction::FcvtWUS { rd, rs1, rm, .. } => {
-                self.registers[rd as usize] = ((self.f_registers[rs1 as usize].round() as u32) as i32) as u64;
-                self.advance();
+            Instruction::FcvtDWU { rd, rs1, .. } | Instruction::FcvtQWU { rd, rs1, .. } => {
+                let val = self.registers[rs1 as usize].to_u64() as u32;
+                self.write_freg(rd, F::from_f64(f64::from(val)));
             },
             Instruction::FmvXW { rd, rs1, .. } => {
-                let rs1_val = (((self.f_registers[rs1 as usize].to_bits() & 0xffffffff) as i32) as i64) as u64;
-                self.registers[rd as usize] = rs1_val;
-                self.advance();
-            },
-            Instruction::FeqS { rd, rs1, rs2, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.registers[rd as usize] = if rs1_val == rs2_val { 1 } else { 0 };
-                self.advance();
-            },
-            Instruction::FltS { rd, rs1, rs2, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.registers[rd as usize] = if rs1_val < rs2_val { 1 } else { 0 };
-                self.advance();
-            },
-            Instruction::FleS { rd, rs1, rs2, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                println!("{:?} == {:?}: {:?}", rs1_val, rs2_val, rs1_val <= rs2_val);
-                self.registers[rd as usize] = if rs1_val <= rs2_val { 1 } else { 0 };
-                self.advance();
-            },
-            Instruction::FclassS { rd, rs1, .. } => {
-                todo!();
-            },
-            Instruction::FcvtSW { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = ((self.registers[rs1 as usize] as i32) as f32) as f64;
-                self.advance();
-            },
-            Instruction::FcvtSWU { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = ((self.registers[rs1 as usize] as u32) as f32) as f64;
+                let bits = self.f_registers[rs1 as usize].to_f32().to_bits();
+                self.registers[rd as usize] = sext(i64::from(bits as i32));
                 self.advance();
             },
             Instruction::FmvWX { rd, rs1, .. } => {
-                let rs1_val = self.registers[rs1 as usize];
-                self.f_registers[rd as usize] = f64::from_bits(self.registers[rs1 as usize] & 0xffff_ffff);
-                self.advance();
-            },
-            Instruction::FcvtLS { rd, rs1, rm, ..} => {
-                self.registers[rd as usize] = (self.f_registers[rs1 as usize] as f32).round() as u64;
-                self.advance();
-            },
-            Instruction::FcvtLUS { rd, rs1, rm, .. } => {
-                self.registers[rd as usize] = (self.f_registers[rs1 as usize] as f32).round() as u64;
-                self.advance();
-            },
-            Instruction::FcvtSL { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = (self.registers[rs1 as usize] as f32) as f64;
-                self.advance();
-            },
-            Instruction::FcvtSLU { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = ((self.registers[rs1 as usize] as u64) as f32) as f64;
-                self.advance();
+                let bits = self.registers[rs1 as usize].to_u64() as u32;
+                self.write_freg(rd, F::from_f32(f32::from_bits(bits)));
             },
-            Instruction::Fld { rd, rs1, imm, .. } => {
-                let addr = self.registers[rs1 as usize];
-                if let Ok(val) = self.bus.read(&addr, 64) {
-                    let f_val = f64::from_bits(val);
-                    self.f_registers[rd as usize] = f_val;
-                }
-                self.advance();
+            Instruction::FcvtSD { rd, rs1, .. } | Instruction::FcvtSQ { rd, rs1, .. } => {
+                self.write_freg(rd, F::from_f32(self.f_registers[rs1 as usize].to_f32()));
             },
-            Instruction::Fsd { rs1, rs2, imm, .. } => {
-                let addr = self.registers[rs1 as usize];
-                let val = self.f_registers[rs2 as usize];
-                self.bus.write(addr, val.to_bits() as u64, 64);
-                self.advance();
+            Instruction::FcvtDS { rd, rs1, .. } | Instruction::FcvtQS { rd, rs1, .. } => {
+                self.write_freg(rd, F::from_f64(f64::from(self.f_registers[rs1 as usize].to_f32())));
             },
-            Instruction::FmaddD { rd, rs1, rs2, rs3, rm, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                let rs3_val = self.f_registers[rs3 as usize];
-                self.f_registers[rd as usize] = rs1_val.mul_add(rs2_val, rs3_val);
-                self.advance();
+            Instruction::FcvtDQ { rd, rs1, .. } | Instruction::FcvtQD { rd, rs1, .. } => {
+                self.write_freg(rd, self.f_registers[rs1 as usize]);
             },
-            Instruction::FmsubD { rd, rs1, rs2, rs3, rm, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                let rs3_val = -self.f_registers[rs3 as usize];
-                self.f_registers[rd as usize] = rs1_val.mul_add(rs2_val, rs3_val);
+            Instruction::FcvtLS { rd, rs1, rm, .. }
+            | Instruction::FcvtLD { rd, rs1, rm, .. }
+            | Instruction::FcvtLQ { rd, rs1, rm, .. } => {
+                let val = float_to_i64(self.f_registers[rs1 as usize].to_f64(), self.rounding_mode(rm));
+                self.registers[rd as usize] = R::from_u64(val);
                 self.advance();
             },
-            Instruction::FnmsubD { rd, rs1, rs2, rs3, rm, .. } => {
-                let rs1_val = -self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                let rs3_val = -self.f_registers[rs3 as usize];
-                self.f_registers[rd as usize] = rs1_val.mul_add(rs2_val, rs3_val);
+            Instruction::FcvtLUS { rd, rs1, rm, .. }
+            | Instruction::FcvtLUD { rd, rs1, rm, .. }
+            | Instruction::FcvtLUQ { rd, rs1, rm, .. } => {
+                let val = float_to_u64(self.f_registers[rs1 as usize].to_f64(), self.rounding_mode(rm));
+                self.registers[rd as usize] = R::from_u64(val);
                 self.advance();
             },
-            Instruction::FnmaddD { rd, rs1, rs2, rs3, rm, .. } => {
-                let rs1_val = -self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                let rs3_val = self.f_registers[rs3 as usize];
-                self.f_registers[rd as usize] = rs1_val.mul_add(rs2_val, rs3_val);
-                self.advance();
+            Instruction::FcvtSL { rd, rs1, .. } | Instruction::FcvtSLU { rd, rs1, .. } => {
+                let val = self.registers[rs1 as usize].to_u64();
+                self.write_freg(rd, F::from_f32(val as f32));
             },
-            Instruction::FaddD { rd, rs1, rs2, rm, .. } => {
-                self.f_registers[rd as usize] = self.f_registers[rs1 as usize] + self.f_registers[rs2 as usize]; 
-                self.advance();
-            },
-            Instruction::FsubD { rd, rs1, rs2, rm, .. } => {
-                self.f_registers[rd as usize] = self.f_registers[rs1 as usize] - self.f_registers[rs2 as usize];
-                self.advance();
-            },
-            Instruction::FmulD { rd, rs1, rs2, rm, .. } => {
-                self.f_registers[rd as usize] = self.f_registers[rs1 as usize] * self.f_registers[rs2 as usize];
-                self.advance();
-            },
-            Instruction::FdivD { rd, rs1, rs2, rm, .. } => {
-                self.f_registers[rd as usize] = self.f_registers[rs1 as usize] / self.f_registers[rs2 as usize];
-                self.advance();
-            },
-            Instruction::FsqrtD { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = self.f_registers[rs1 as usize].sqrt();
-                self.advance();
-            },
-            Instruction::FsgnjD { rd, rs1, rs2, .. } => {
-                self.f_registers[rd as usize] = self.f_registers[rs1 as usize].copysign(self.f_registers[rs2 as usize]);
-                self.advance();   
-            },
-            Instruction::FsgnjnD { rd, rs1, rs2, .. } => {
-                self.f_registers[rd as usize] = self.f_registers[rs1 as usize].copysign(-self.f_registers[rs2 as usize]);
-                self.advance();
-            },
-            Instruction::FsgnjxD { rd, rs1, rs2, .. } => {
-                let sign_1 = self.f_registers[rs1 as usize].to_bits() & 0x8000_0000_0000_0000;
-                let sign_2 = self.f_registers[rs2 as usize].to_bits() & 0x8000_0000_0000_0000;
-                let other = self.f_registers[rs1 as usize].to_bits() & 0x7fff_ffff_ffff_ffff;
-                self.f_registers[rd as usize] = f64::from_bits((sign_1 ^ sign_2) | other);
-                self.advance();
-            },
-            Instruction::FminD { rd, rs1, rs2, .. } => {
-                self.f_registers[rd as usize] = self.f_registers[rs1 as usize].min(self.f_registers[rs2 as usize]);
-                self.advance();
-            },
-            Instruction::FmaxD { rd, rs1, rs2, .. } => {
-                self.f_registers[rd as usize] = self.f_registers[rs1 as usize].max(self.f_registers[rs2 as usize]);
-                self.advance();
-            },
-            Instruction::FcvtSD { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = self.f_registers[rs1 as usize];
-                self.advance();
-            },
-            Instruction::FcvtDS { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = (self.f_registers[rs1 as usize] as f32) as f64;
-                self.advance();
-            },
-            Instruction::FeqD { rd, rs1, rs2, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.registers[rd as usize] = if rs1_val == rs2_val { 1 } else { 0 };
-                self.advance();
-            },
-            Instruction::FltD { rd, rs1, rs2, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.registers[rd as usize] = if  rs1_val < rs2_val { 1 } else { 0 };
-                self.advance();
-            },
-            Instruction::FleD { rd, rs1, rs2, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.registers[rd as usize] = if  rs1_val <= rs2_val { 1 } else { 0 };
-                self.advance();
-            },
-            Instruction::FclassD { rd, rs1, ..} => {},
-            Instruction::FcvtWD { rd, rs1, rm, .. } => {
-                self.registers[rd as usize] = (self.f_registers[rs1 as usize].round() as i32) as u64;
-                self.advance();
-            },
-            Instruction::FcvtWUD { rd, rs1, rm, .. } => {
-                self.registers[rd as usize] = ((self.f_registers[rs1 as usize].round() as u32) as i32) as u64;
-                self.advance();
-            },
-            Instruction::FcvtDW { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = (self.registers[rs1 as usize] as i32) as f64;
-                self.advance();
-            },
-            Instruction::FcvtDWU { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = (self.registers[rs1 as usize] as u32) as f64;
-                self.advance();
-            },
-            Instruction::FcvtLD { rd, rs1, rm, .. } => {
-                self.registers[rd as usize] = (self.f_registers[rs1 as usize].round()) as u64;
-                self.advance();
-            },
-            Instruction::FcvtLUD { rd, rs1, rm, .. } => {
-                self.registers[rd as usize] = (self.f_registers[rs1 as usize].round()) as u64;
-                self.advance();
+            Instruction::FcvtDL { rd, rs1, .. }
+            | Instruction::FcvtDLU { rd, rs1, .. }
+            | Instruction::FcvtQL { rd, rs1, .. }
+            | Instruction::FcvtQLU { rd, rs1, .. } => {
+                let val = self.registers[rs1 as usize].to_u64();
+                self.write_freg(rd, F::from_f64(val as f64));
             },
             Instruction::FmvXD { rd, rs1, .. } => {
-                self.registers[rd as usize] = (self.f_registers[rs1 as usize].to_bits());
-                self.advance();
-            },
-            Instruction::FcvtDL { rd, rs1, .. } => {
-                self.f_registers[rd as usize] = self.registers[rs1 as usize] as f64;
-                self.advance();
-            },
-            Instruction::FcvtDLU { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = self.registers[rs1 as usize] as f64;
+                self.registers[rd as usize] = R::from_u64(self.f_registers[rs1 as usize].to_f64().to_bits());
                 self.advance();
             },
             Instruction::FmvDX { rd, rs1, .. } => {
-                self.registers[rd as usize] = self.f_registers[rs1 as usize].to_bits();
-                self.advance();
-            },
-            Instruction::Flq { rd, rs1, imm, .. } => {
-                let addr = self.registers[rs1 as usize];
-                if let Ok(val) = self.bus.read(&addr, 64) {
-                    let val = f64::from_bits(val);
-                    self.f_registers[rd as usize] = val;
-                }
-                self.advance();
-            },
-            Instruction::Fsq { rs1, rs2, imm, .. } => {
-                let addr = self.registers[rs1 as usize];
-                let val = self.f_registers[rs2 as usize].to_bits() as u64;
-                self.bus.write(addr, val, 64);
-                self.advance();
-            },
-            Instruction::FmaddQ { rd, rs1, rs2, rs3, rm, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                let rs3_val = self.f_registers[rs3 as usize];
-                self.f_registers[rd as usize] = rs1_val.mul_add(rs2_val, rs3_val);
-                self.advance();
-            },
-            Instruction::FmsubQ { rd, rs1, rs2, rs3, rm, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                let rs3_val = -self.f_registers[rs3 as usize];
-                self.f_registers[rd as usize] = rs1_val.mul_add(rs2_val, rs3_val);
-                self.advance();
-            },
-            Instruction::FnmsubQ { rd, rs1, rs2, rs3, rm, .. } => {
-                let rs1_val = -self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                let rs3_val = -self.f_registers[rs3 as usize];
-                self.f_registers[rd as usize] = rs1_val.mul_add(rs2_val, rs3_val);
-                self.advance();
-            },
-            Instruction::FnmaddQ { rd, rs1, rs2, rs3, rm, .. } => {
-                let rs1_val = -self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                let rs3_val = self.f_registers[rs3 as usize];
-                self.f_registers[rd as usize] = rs1_val.mul_add(rs2_val, rs3_val);
-                self.advance();
-            },
-            Instruction::FaddQ { rd, rs1, rs2, rm, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.f_registers[rd as usize] = rs1_val + rs2_val;
-                self.advance();
-            },
-            Instruction::FsubQ { rd, rs1, rs2, rm, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.f_registers[rd as usize] = rs1_val - rs2_val;
-                self.advance();
-            },
-            Instruction::FmulQ { rd, rs1, rs2, rm, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.f_registers[rd as usize] = rs1_val * rs2_val;
-                self.advance();
-            },
-            Instruction::FdivQ { rd, rs1, rs2, rm, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.f_registers[rd as usize] = rs1_val / rs2_val;
-                self.advance();
-            },
-            Instruction::FsqrtQ { rd, rs1, rm, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                self.f_registers[rd as usize] = rs1_val.sqrt();
-                self.advance();
-            },
-            Instruction::FsgnjQ { rd, rs1, rs2, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.f_registers[rd as usize] = rs1_val.copysign(rs2_val);
-                self.advance();
-            },
-            Instruction::FsgnjnQ { rd, rs1, rs2, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = -self.f_registers[rs2 as usize];
-                self.f_registers[rd as usize] = rs1_val.copysign(rs2_val);
-                self.advance();
-            },
-            Instruction::FsgnjxQ { rd, rs1, rs2, .. } => {
-                let sign_1 = self.f_registers[rs1 as usize].to_bits() & 0x8000_0000_0000_0000;
-                let sign_2 = self.f_registers[rs2 as usize].to_bits() & 0x8000_0000_0000_0000;
-                let other = self.f_registers[rs1 as usize].to_bits() & 0x7fff_ffff_ffff_ffff;
-                self.f_registers[rd as usize] = f64::from_bits((sign_1 ^ sign_2) | other);
-                self.advance();
-            },
-            Instruction::FminQ { rd, rs1, rs2, .. } => {
-                self.f_registers[rd as usize] = self.f_registers[rs1 as usize].min(self.f_registers[rs2 as usize]);
-                self.advance();
-            },
-            Instruction::FmaxQ { rd, rs1, rs2, .. } => {
-                self.f_registers[rd as usize] = self.f_registers[rs1 as usize].max(self.f_registers[rs2 as usize]);
-                self.advance();
-            },
-            Instruction::FcvtSQ { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = self.f_registers[rs1 as usize];
-                self.advance();
-            },
-            Instruction::FcvtQS { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = (self.f_registers[rs1 as usize]);
-                self.advance();
+                let bits = self.registers[rs1 as usize].to_u64();
+                self.write_freg(rd, F::from_f64(f64::from_bits(bits)));
             },
-            Instruction::FcvtDQ { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = (self.f_registers[rs1 as usize] as f32) as f64;
-                self.advance();
-            },
-            Instruction::FcvtQD { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = (self.f_registers[rs1 as usize] as f32) as f64;
-                self.advance();
-            },
-            Instruction::FeqQ { rd, rs1, rs2, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.registers[rd as usize] = if rs1_val == rs2_val { 1 } else { 0 };
-                self.advance();
-            },
-            Instruction::FltQ { rd, rs1, rs2, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.registers[rd as usize] = if rs1_val < rs2_val { 1 } else { 0 };
-                self.advance();
-            },
-            Instruction::FleQ { rd, rs1, rs2, .. } => {
-                let rs1_val = self.f_registers[rs1 as usize];
-                let rs2_val = self.f_registers[rs2 as usize];
-                self.registers[rd as usize] = if rs1_val <= rs2_val { 1 } else { 0 };
-                self.advance();
-            },
-            Instruction::FclassQ { rd, rs1, .. } => {
-                //TODO: Need to add classes enum and class logic execution
-                self.advance();
-            },
-            Instruction::FcvtWQ { rd, rs1, rm, .. } => {
-                self.registers[rd as usize] = (self.f_registers[rs1 as usize].round() as i32) as u64;
-                self.advance();
-            },
-            Instruction::FcvtWUQ { rd, rs1, rm, .. } => {
-                self.registers[rd as usize] = ((self.f_registers[rs1 as usize].round() as u32) as i32) as u64;
-                self.advance();
-            },
-            Instruction::FcvtQW { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = (self.registers[rs1 as usize] as i32) as f64;
-                self.advance();
-            },
-            Instruction::FcvtQWU { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = (self.registers[rs1 as usize] as u32) as f64;
-                self.advance();
-            },
-            Instruction::FcvtLQ { rd, rs1, rm, .. } => {
-                self.registers[rd as usize] = (self.f_registers[rs1 as usize].round()) as u64;
-                self.advance();
-            },
-            Instruction::FcvtLUQ { rd, rs1, rm, .. } => {
-                self.registers[rd as usize] = (self.f_registers[rs1 as usize].round()) as u64;
-                self.advance();    
-            },
-            Instruction::FcvtQL { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = self.registers[rs1 as usize] as f64;
-                self.advance();
-            },
-            Instruction::FcvtQLU { rd, rs1, rm, .. } => {
-                self.f_registers[rd as usize] = self.registers[rs1 as usize] as f64;
-                self.advance();
-            },
-            _ => { /* Return an error here, and some other places */ }
+            _ => return Err(Exception::Invalid(inst as u64)),
         }
+
+        Ok(())
     }
 
-    pub fn load_program(&mut self, code: Vec<u8>) -> Result<(), Exception> {
-        if code.len() > 4096usize {
-            return Err(Exception::StackSizeExceeded);
+    // The address rs1 + imm of a load or store.
+    fn addr(&self, rs1: Register, imm: i32) -> u64 {
+        self.registers[rs1 as usize].oflow_add(&sext(i64::from(imm))).to_u64()
+    }
+
+    // The low words of rs1 and rs2.
+    fn words(&self, rs1: Register, rs2: Register) -> (u32, u32) {
+        (self.registers[rs1 as usize].to_u64() as u32, self.registers[rs2 as usize].to_u64() as u32)
+    }
+
+    // The shift amount in rs2, the low log2(XLEN) bits.
+    fn shamt(&self, rs2: Register) -> u32 {
+        (self.registers[rs2 as usize].to_u64() & u64::from(R::SHIFT_MASK)) as u32
+    }
+
+    fn branch(&mut self, taken: bool, imm: i32) {
+        if taken {
+            self.pc = self.pc.oflow_add(&sext(i64::from(imm)));
+        } else {
+            self.advance();
+        }
+    }
+
+    // Loads the value at the address in rs1 into rd and reserves the address.
+    fn load_reserved(&mut self, rd: Register, rs1: Register, size: u8) -> Result<(), Exception> {
+        let addr = self.registers[rs1 as usize].to_u64();
+        if !addr.is_multiple_of(u64::from(size / 8)) {
+            self.tval = addr;
+            return Err(Exception::LoadAddressMisaligned);
+        }
+        let val = self.load(addr, size)?;
+        self.registers[rd as usize] = sext(sign_extend(val, size));
+        self.res.push(addr);
+        self.advance();
+        Ok(())
+    }
+
+    // Stores rs2 at the address in rs1 if it is still reserved, rd is zero
+    // when the store happened. Either way the reservation is dropped.
+    fn store_conditional(&mut self, rd: Register, rs1: Register, rs2: Register, size: u8) -> Result<(), Exception> {
+        let addr = self.registers[rs1 as usize].to_u64();
+        if !addr.is_multiple_of(u64::from(size / 8)) {
+            self.tval = addr;
+            return Err(Exception::StoreAMOAddressMisaligned);
+        }
+        if self.res.contains(&addr) {
+            self.res.retain(|x| *x != addr);
+            self.store(addr, self.registers[rs2 as usize].to_u64(), size)?;
+            self.registers[rd as usize] = R::zero();
+        } else {
+            self.res.retain(|x| *x != addr);
+            self.registers[rd as usize] = R::one();
+        }
+        self.advance();
+        Ok(())
+    }
+
+    // Reads the value at the address in rs1, writes op(value, rs2) back and
+    // returns the original value, sign extended, in rd.
+    fn amo(&mut self, rd: Register, rs1: Register, rs2: Register, size: u8, op: impl Fn(u64, u64) -> u64) -> Result<(), Exception> {
+        let addr = self.registers[rs1 as usize].to_u64();
+        if !addr.is_multiple_of(u64::from(size / 8)) {
+            self.tval = addr;
+            return Err(Exception::StoreAMOAddressMisaligned);
+        }
+        let mem = self.load(addr, size)?;
+        self.store(addr, op(mem, self.registers[rs2 as usize].to_u64()), size)?;
+        self.registers[rd as usize] = sext(sign_extend(mem, size));
+        self.advance();
+        Ok(())
+    }
+
+    fn write_freg(&mut self, reg: Register, val: F) {
+        self.f_registers[reg as usize] = val;
+        self.advance();
+    }
+
+    // The dynamic rounding mode (7) uses the mode in frm.
+    fn rounding_mode(&self, rm: u32) -> u32 {
+        if rm == 7 {
+            self.csr.frm as u32
+        } else {
+            rm
+        }
+    }
+}
+
+// A sign extended value truncated to XLEN.
+fn sext<R: RegisterValue>(val: i64) -> R {
+    R::from_u64(val as u64)
+}
+
+// Sign extends a word or doubleword read from memory.
+fn sign_extend(val: u64, size: u8) -> i64 {
+    if size == 32 {
+        i64::from(val as i32)
+    } else {
+        val as i64
+    }
+}
+
+// Rounds to an integral value using the rounding mode of a float
+// instruction, after the dynamic mode has been resolved from frm.
+fn round_rm(val: f64, rm: u32) -> f64 {
+    match rm {
+        1 => val.trunc(),
+        2 => val.floor(),
+        3 => val.ceil(),
+        4 => val.round(),
+        _ => val.round_ties_even(),
+    }
+}
+
+// Float to integer conversions saturate, NaN converts to the largest value.
+fn float_to_i32(val: f64, rm: u32) -> u32 {
+    if val.is_nan() {
+        return i32::MAX as u32;
+    }
+    round_rm(val, rm) as i32 as u32
+}
+
+fn float_to_u32(val: f64, rm: u32) -> u32 {
+    if val.is_nan() {
+        return u32::MAX;
+    }
+    round_rm(val, rm) as u32
+}
+
+fn float_to_i64(val: f64, rm: u32) -> u64 {
+    if val.is_nan() {
+        return i64::MAX as u64;
+    }
+    round_rm(val, rm) as i64 as u64
+}
+
+fn float_to_u64(val: f64, rm: u32) -> u64 {
+    if val.is_nan() {
+        return u64::MAX;
+    }
+    round_rm(val, rm) as u64
+}
+
+// The fclass result: a single bit set for the class of the value.
+fn fclass(category: FpCategory, negative: bool, quiet: bool) -> u32 {
+    let bit = match (category, negative) {
+        (FpCategory::Infinite, true) => 0,
+        (FpCategory::Normal, true) => 1,
+        (FpCategory::Subnormal, true) => 2,
+        (FpCategory::Zero, true) => 3,
+        (FpCategory::Zero, false) => 4,
+        (FpCategory::Subnormal, false) => 5,
+        (FpCategory::Normal, false) => 6,
+        (FpCategory::Infinite, false) => 7,
+        (FpCategory::Nan, _) if !quiet => 8,
+        (FpCategory::Nan, _) => 9,
+    };
+    1 << bit
+}
+
+
+
+
+// Syscall buffers go byte by byte through the hart's own load and store, so
+// they get the same translation, PMP checks and faults as the guest.
+impl<R: RegisterValue + Copy, F: FloatValue, M: Memory<RegValue = u64, Bytes = Vec<u8>>> GuestMemory for SoftThread<R, F, M> {
+    fn read_array(&mut self, addr: u64, len: u64) -> Result<Vec<u8>, Exception> {
+        (0..len).map(|i| self.load(addr.wrapping_add(i), 8).map(|byte| byte as u8)).collect()
+    }
+
+    fn write_array(&mut self, addr: u64, bytes: &[u8]) -> Result<(), Exception> {
+        for (i, byte) in (0..).zip(bytes) {
+            self.store(addr.wrapping_add(i), u64::from(*byte), 8)?;
+        }
+        Ok(())
+    }
+}
+
+impl<R: RegisterValue + Copy, F: FloatValue, M: Memory<RegValue = u64, Bytes = Vec<u8>>> Machine for SoftThread<R, F, M> {
+    type Reg = R;
+    type Mem = M;
+    type Error = Exception;
+
+    fn pc(&mut self) -> &Self::Reg {
+        &self.pc
+    }
+
+    // Stages a new pc that takes effect once the current instruction
+    // completes, see `commit_pc`.
+    fn update_pc(&mut self, pc: Self::Reg) {
+        self.next_pc = Some(pc);
+    }
+
+    fn commit_pc(&mut self) {
+        if let Some(pc) = self.next_pc.take() {
+            self.pc = pc;
+        }
+    }
+
+    fn memory(&self) -> &Self::Mem {
+        &self.bus
+    }
+
+    fn memory_mut(&mut self) -> &mut Self::Mem {
+        &mut self.bus
+    }
+
+    fn registers(&self) -> &[Self::Reg] {
+        &self.registers[..N_REGISTERS]
+    }
+
+    fn set_register(&mut self, idx: usize, value: Self::Reg) {
+        if idx != 0 && idx < N_REGISTERS {
+            self.registers[idx] = value;
+        }
+    }
+
+    fn base(&self) -> u8 {
+        match self.enc_table.get_base() {
+            Base::I32 => 32,
+            Base::I64 => 64,
+        }
+    }
+
+    // The extension bits of misa.
+    fn ext(&self) -> u32 {
+        self.enc_table.get_extensions().letters() & !Self::missing_letters()
+    }
+
+    fn version(&self) -> u32 {
+        VERSION
+    }
+
+    // Dispatches the call in a7 to the registered syscall handlers. Calls
+    // no handler serves are handed to the guest's trap handler.
+    fn ecall(&mut self) -> Result<(), Self::Error> {
+        self.add_cycles(self.costs.syscall)?;
+        let mut call = Syscall {
+            number: self.registers[usize::from(Register::from(SYSCALL_NUMBER))].to_u64(),
+            ..Syscall::default()
+        };
+        for (arg, reg) in call.args.iter_mut().zip(SYSCALL_ARGS) {
+            *arg = self.registers[usize::from(Register::from(reg))].to_u64();
+        }
+
+        // The handlers reach guest memory through the hart, so they are
+        // taken out while they run.
+        let mut syscalls = std::mem::take(&mut self.syscalls);
+        let outcome = syscalls.iter_mut().find_map(|handler| handler.ecall(&call, self).transpose());
+        self.syscalls = syscalls;
+        let outcome = outcome.transpose()?;
+
+        let [a0, a1] = SYSCALL_RETURNS.map(|reg| usize::from(Register::from(reg)));
+        match outcome {
+            Some(SyscallOutcome::Return(r0, r1)) => {
+                self.registers[a0] = R::from_u64(r0);
+                self.registers[a1] = R::from_u64(r1);
+            }
+            Some(SyscallOutcome::Halt(code)) => {
+                self.registers[a0] = R::from_u64(code);
+                self.running = false;
+            }
+            None => {
+                return Err(match self.privilege {
+                    Privilege::User => Exception::EnvironmentCallFromUMode,
+                    Privilege::Supervisor => Exception::EnvironmentCallFromSMode,
+                    Privilege::Machine => Exception::EnvironmentCallFromMMode,
+                });
+            }
         }
 
-        self.program = code;
-        
         Ok(())
     }
+
+    fn ebreak(&mut self) -> Result<(), Self::Error> {
+        Err(Exception::Breakpoint)
+    }
 }
 
+impl<R: RegisterValue + Copy, F: FloatValue, M: Memory<RegValue = u64, Bytes = Vec<u8>>> Support for SoftThread<R, F, M> {
+    type Bytes = Vec<u8>;
+
+    fn cycles(&self) -> u64 {
+        self.cycles
+    }
+
+    fn set_cycles(&mut self, cycles: u64) {
+        self.cycles = cycles;
+    }
+
+    fn max_cycles(&self) -> u64 {
+        self.max_cycles
+    }
+
+    fn set_max_cycles(&mut self, max_cycles: u64) {
+        self.max_cycles = max_cycles;
+    }
+
+    fn running(&self) -> bool {
+        self.running
+    }
+
+    fn set_running(&mut self, running: bool) {
+        self.running = running;
+    }
+
+    // Puts the hart back into its power-on state with a fresh cycle budget.
+    // Memory and the loaded program are kept so the program can be re-run.
+    fn reset(&mut self, max_cycles: u64) {
+        self.registers = [R::zero(); 33];
+        self.registers[2] = R::from_u64(self.layout.stack().end);
+        self.f_registers = [F::zero(); 33];
+        self.csr = CsrFile::new(self.enc_table.misa() & !u64::from(Self::missing_letters()));
+        self.privilege = Privilege::Machine;
+        self.mmu = Mmu::new();
+        self.pc = R::from_u64(self.layout.base);
+        self.next_pc = None;
+        self.res.clear();
+        self.tval = 0;
+        self.cycles = 0;
+        self.max_cycles = max_cycles;
+        self.reset_signal = true;
+    }
+
+    // Returns whether the hart was reset since the last call and clears the signal.
+    fn reset_signal(&mut self) -> bool {
+        let signal = self.reset_signal;
+        self.reset_signal = false;
+        signal
+    }
+
+    fn add_cycles(&mut self, cycles: u64) -> Result<(), Self::Error> {
+        let total = self.cycles.checked_add(cycles).ok_or(Exception::OutOfGas(self.cycles))?;
+        if total > self.max_cycles {
+            return Err(Exception::OutOfGas(self.cycles));
+        }
+        self.cycles = total;
+        Ok(())
+    }
+
+    fn add_cycles_no_check(&mut self, cycles: u64) -> Result<(), Self::Error> {
+        self.cycles = self.cycles.wrapping_add(cycles);
+        Ok(())
+    }
+
+    // Places every PT_LOAD segment at its virtual address, see `load_segments`.
+    fn load_inner_elf(&mut self, program: &Self::Bytes, update_pc: bool) -> Result<(), Self::Error> {
+        self.load_elf(program, update_pc).map(|_| ())
+    }
+
+    // Returns the number of bytes the loadable segments occupy in memory.
+    fn load_elf(&mut self, program: &Self::Bytes, update_pc: bool) -> Result<u64, Self::Error> {
+        let elf = self.parse_elf(program)?;
+        self.load_segments(program, &elf)?;
+
+        self.program = program.clone();
+        if update_pc {
+            self.update_pc(R::from_u64(elf.entry));
+            self.commit_pc();
+        }
+        Ok(elf.loadable().map(|ph| ph.memsz).sum())
+    }
+
+    // Lays out argc, the argv pointers and the argument strings at the top of
+    // the stack region [start, start + size) and points sp at argc. Returns the
+    // number of bytes of stack in use. argc and the pointers are XLEN wide.
+    fn init_stack(&mut self, args: &[Self::Bytes], start: u64, size: u64) -> Result<u64, Self::Error> {
+        let xlen = u64::from(R::BITS / 8);
+        let top = start.checked_add(size).ok_or(Exception::StackSizeExceeded)?;
+        let mut sp = top;
+        let mut values = vec![args.len() as u64];
+        for arg in args {
+            let len = arg.len() as u64 + 1;
+            sp = sp.checked_sub(len).filter(|sp| *sp >= start).ok_or(Exception::StackSizeExceeded)?;
+            let mut bytes = arg.clone();
+            bytes.push(0);
+            self.bus.write_array(sp, bytes).map_err(|_| Exception::StoreAMOAccessFault)?;
+            values.push(sp);
+        }
+        values.push(0);
+
+        // sp must stay 16 byte aligned once argc and argv are pushed.
+        let frame = values.len() as u64 * xlen;
+        sp = sp.checked_sub(frame).filter(|sp| *sp >= start).ok_or(Exception::StackSizeExceeded)?;
+        sp &= !0xf;
+        if sp < start {
+            return Err(Exception::StackSizeExceeded);
+        }
+        for (i, value) in values.iter().enumerate() {
+            self.bus.write(sp + (i as u64) * xlen, *value, R::BITS).map_err(|_| Exception::StoreAMOAccessFault)?;
+        }
 
+        self.set_register(2, R::from_u64(sp));
+        Ok(top - sp)
+    }
 
+    fn code(&self) -> &Self::Bytes {
+        &self.program
+    }
+}
 
 impl Default for SoftThread<u64, f64, Dram> {
     fn default() -> SoftThread<u64, f64, Dram> {
         let enc_table = EncodingTable::default();
-        SoftThread::<u64, f64, Dram>::new(enc_table)
+        SoftThread::<u64, f64, Dram>::new(enc_table, MemoryLayout::default())
     }
 }
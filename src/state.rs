@@ -1,5 +1,6 @@
 use std::hash::Hash;
 use std::error::Error;
+use std::collections::BTreeMap;
 
 
 pub trait StateObject {
@@ -10,3 +11,90 @@ pub trait StateObject {
     fn get_code(&self, addr: &Self::Address) -> Result<Self::StateResult, Self::StateError>;
 }
 
+// State that also keeps the key-value storage slots of each contract.
+pub trait StorageObject: StateObject {
+    fn get_slot(&self, addr: &Self::Address, key: &[u8]) -> Result<Option<Vec<u8>>, Self::StateError>;
+    fn set_slot(&mut self, addr: &Self::Address, key: &[u8], value: &[u8]) -> Result<(), Self::StateError>;
+    fn delete_slot(&mut self, addr: &Self::Address, key: &[u8]) -> Result<(), Self::StateError>;
+}
+
+/// A write to a storage slot that has not reached the state yet.
+#[derive(Clone, Debug, PartialEq)]
+pub enum SlotChange {
+    Set(Vec<u8>),
+    Delete,
+}
+
+/// The storage writes of one execution, keyed by slot.
+pub type StorageDiff = BTreeMap<Vec<u8>, SlotChange>;
+
+/// Buffers the storage writes of a contract on top of the state. Reads see
+/// the pending writes, nothing reaches the state until `commit` is called
+/// and `rollback` throws the writes away.
+#[derive(Debug)]
+pub struct Journal<S: StorageObject> {
+    state: S,
+    addr: S::Address,
+    changes: StorageDiff,
+}
+
+impl<S: StorageObject> Journal<S> {
+    pub fn new(state: S, addr: S::Address) -> Journal<S> {
+        Journal {
+            state,
+            addr,
+            changes: StorageDiff::new(),
+        }
+    }
+
+    pub fn get(&self, key: &[u8]) -> Result<Option<Vec<u8>>, S::StateError> {
+        match self.changes.get(key) {
+            Some(SlotChange::Set(value)) => Ok(Some(value.clone())),
+            Some(SlotChange::Delete) => Ok(None),
+            None => self.state.get_slot(&self.addr, key),
+        }
+    }
+
+    pub fn set(&mut self, key: &[u8], value: &[u8]) {
+        self.changes.insert(key.to_vec(), SlotChange::Set(value.to_vec()));
+    }
+
+    pub fn delete(&mut self, key: &[u8]) {
+        self.changes.insert(key.to_vec(), SlotChange::Delete);
+    }
+
+    // The writes made since the last commit or rollback.
+    pub fn diff(&self) -> &StorageDiff {
+        &self.changes
+    }
+
+    pub fn rollback(&mut self) {
+        self.changes.clear();
+    }
+
+    pub fn state(&self) -> &S {
+        &self.state
+    }
+
+    pub fn into_state(self) -> S {
+        self.state
+    }
+}
+
+impl<S: StorageObject + Clone> Journal<S> {
+    // Applies the pending writes to the state and hands them back. The
+    // writes go to a copy of the state first, so a failed write leaves the
+    // state and the pending writes as they were.
+    pub fn commit(&mut self) -> Result<StorageDiff, S::StateError> {
+        let mut state = self.state.clone();
+        for (key, change) in self.changes.iter() {
+            match change {
+                SlotChange::Set(value) => state.set_slot(&self.addr, key, value)?,
+                SlotChange::Delete => state.delete_slot(&self.addr, key)?,
+            }
+        }
+
+        self.state = state;
+        Ok(std::mem::take(&mut self.changes))
+    }
+}
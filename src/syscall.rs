@@ -0,0 +1,138 @@
+#![allow(unused, unused_mut, dead_code)]
+use crate::exceptions::Exception;
+use crate::register::{FunctionArgument, FunctionArgumentReturnValues, RegisterAbi};
+use crate::state::{Journal, StorageObject};
+use std::cell::RefCell;
+use std::fmt::Debug;
+use std::rc::Rc;
+
+// Ends the program, a0 holds the exit code.
+pub const EXIT: u64 = 93;
+
+// Contract storage
+// a0/a1: key pointer and length, a2/a3: value buffer pointer and capacity.
+// Returns the length of the value in a0, or u64::MAX if the slot is empty.
+pub const STORAGE_GET: u64 = 0x1000;
+// a0/a1: key pointer and length, a2/a3: value pointer and length.
+pub const STORAGE_SET: u64 = 0x1001;
+// a0/a1: key pointer and length.
+pub const STORAGE_DELETE: u64 = 0x1002;
+
+// The register holding the syscall number.
+pub const SYSCALL_NUMBER: RegisterAbi = RegisterAbi::A7(FunctionArgument);
+
+// The registers holding the syscall arguments, in order.
+pub const SYSCALL_ARGS: [RegisterAbi; 6] = [
+    RegisterAbi::A0(FunctionArgumentReturnValues),
+    RegisterAbi::A1(FunctionArgumentReturnValues),
+    RegisterAbi::A2(FunctionArgument),
+    RegisterAbi::A3(FunctionArgument),
+    RegisterAbi::A4(FunctionArgument),
+    RegisterAbi::A5(FunctionArgument),
+];
+
+// The registers the syscall results are written back to.
+pub const SYSCALL_RETURNS: [RegisterAbi; 2] = [
+    RegisterAbi::A0(FunctionArgumentReturnValues),
+    RegisterAbi::A1(FunctionArgumentReturnValues),
+];
+
+/// An environment call as read from the guest registers.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Syscall {
+    pub number: u64,
+    pub args: [u64; 6],
+}
+
+/// What the hart should do once a syscall has been handled.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum SyscallOutcome {
+    // Resume the guest with the values written to a0 and a1.
+    Return(u64, u64),
+    // Stop the hart, the value is written to a0 as the exit code.
+    Halt(u64),
+}
+
+/// Guest memory as the calling hart sees it: addresses are translated and
+/// checked against PMP like the guest's own loads and stores, and faults are
+/// raised as theirs.
+pub trait GuestMemory {
+    fn read_array(&mut self, addr: u64, len: u64) -> Result<Vec<u8>, Exception>;
+    fn write_array(&mut self, addr: u64, bytes: &[u8]) -> Result<(), Exception>;
+}
+
+/// A host interface the guest reaches through `ecall`. Handlers are asked
+/// in the order they were registered, the first one to return `Some`
+/// serves the call.
+pub trait Syscalls: Debug {
+    fn ecall(&mut self, call: &Syscall, memory: &mut dyn GuestMemory) -> Result<Option<SyscallOutcome>, Exception>;
+}
+
+/// Exposes the storage of a contract to the guest. The journal is shared
+/// with the host, which commits it once the execution succeeded or rolls
+/// it back if it failed, see `Cpu::run_with_journal`.
+pub struct StorageSyscalls<S: StorageObject> {
+    journal: Rc<RefCell<Journal<S>>>,
+}
+
+impl<S: StorageObject + Debug> Debug for StorageSyscalls<S> where S::Address: Debug {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("StorageSyscalls").field("journal", &self.journal).finish()
+    }
+}
+
+impl<S: StorageObject> StorageSyscalls<S> {
+    pub fn new(journal: Rc<RefCell<Journal<S>>>) -> StorageSyscalls<S> {
+        StorageSyscalls { journal }
+    }
+}
+
+impl<S> Syscalls for StorageSyscalls<S>
+where
+    S: StorageObject + Debug,
+    S::Address: Debug,
+{
+    fn ecall(&mut self, call: &Syscall, memory: &mut dyn GuestMemory) -> Result<Option<SyscallOutcome>, Exception> {
+        let [key_ptr, key_len, val_ptr, val_len, _, _] = call.args;
+        let key = match call.number {
+            STORAGE_GET | STORAGE_SET | STORAGE_DELETE => memory.read_array(key_ptr, key_len)?,
+            _ => return Ok(None),
+        };
+
+        let mut journal = self.journal.borrow_mut();
+        match call.number {
+            STORAGE_GET => {
+                let value = journal.get(&key).map_err(|e| Exception::State(e.to_string()))?;
+                match value {
+                    Some(mut value) => {
+                        let len = value.len() as u64;
+                        value.truncate(std::cmp::min(len, val_len) as usize);
+                        memory.write_array(val_ptr, &value)?;
+                        Ok(Some(SyscallOutcome::Return(len, 0)))
+                    }
+                    None => Ok(Some(SyscallOutcome::Return(u64::MAX, 0))),
+                }
+            }
+            STORAGE_SET => {
+                let value = memory.read_array(val_ptr, val_len)?;
+                journal.set(&key, &value);
+                Ok(Some(SyscallOutcome::Return(0, 0)))
+            }
+            _ => {
+                journal.delete(&key);
+                Ok(Some(SyscallOutcome::Return(0, 0)))
+            }
+        }
+    }
+}
+
+/// Halts the hart on `EXIT`. Every `Cpu` registers it, so a program can end
+/// without a host handler.
+#[derive(Debug, Default)]
+pub struct ExitSyscall;
+
+impl Syscalls for ExitSyscall {
+    fn ecall(&mut self, call: &Syscall, memory: &mut dyn GuestMemory) -> Result<Option<SyscallOutcome>, Exception> {
+        Ok((call.number == EXIT).then_some(SyscallOutcome::Halt(call.args[0])))
+    }
+}
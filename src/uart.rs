@@ -1,23 +1,256 @@
+use std::collections::VecDeque;
+use std::io::Write;
+use std::sync::mpsc::{Receiver, Sender};
+use std::sync::{Arc, Mutex};
+use crate::memory::{MemError, BYTE};
+
+// A 16550 compatible UART. Registers are given by their offset from
+// `bus::UART_BASE`.
 pub trait Uart: Default {
-    const UART_IRQ: u64;
-    const UART_RECEIVING_HOLDING_REGISTER: u64;
-    const UART_TRANSMIT_HOLDING_REGISTER: u64;
-    const UART_INTERRUPT_ENABLE_REGISTER: u64;
-    const UART_FIFO_CONTROL_REGISTER: u64;
-    const UART_INTERRUPT_STATUS_REGISTER: u64;
-    const UART_LINE_STATUS_REGISTER: u64;
-    const UART_LINE_STATUS_REGISTER_RECEIVER: u64;
-    const UART_LINE_STATUS_REGISTER_SENDER: u64;
+    const UART_IRQ: u64 = 10;
+    const UART_RECEIVING_HOLDING_REGISTER: u64 = 0;
+    const UART_TRANSMIT_HOLDING_REGISTER: u64 = 0;
+    const UART_INTERRUPT_ENABLE_REGISTER: u64 = 1;
+    const UART_FIFO_CONTROL_REGISTER: u64 = 2;
+    const UART_INTERRUPT_STATUS_REGISTER: u64 = 2;
+    const UART_LINE_STATUS_REGISTER: u64 = 5;
+    const UART_LINE_STATUS_REGISTER_RECEIVER: u64 = 1;
+    const UART_LINE_STATUS_REGISTER_SENDER: u64 = 1 << 5;
 
     type Interrupting;
     type ReceiverTransmitter;
     type Exception;
 
     fn is_interrupting(&self) -> bool;
+    // Pulls input from the host into the receiver, once per tick.
+    fn receive(&mut self) {}
+    // Reads can change the state of the UART, reading the receiver
+    // holding register takes the byte out of it.
     fn read(&mut self, index: u64, size: u8) -> Result<u64, Self::Exception>;
-    fn write(&mut self, index: u64, value: u8, size: u8) -> Result<(), Exception>; 
+    fn write(&mut self, index: u64, value: u8, size: u8) -> Result<(), Self::Exception>; 
     fn new() -> Self {
         Self::default()
     }
 }
 
+// Registers past those of the trait.
+const LINE_CONTROL_REGISTER: u64 = 3;
+const MODEM_CONTROL_REGISTER: u64 = 4;
+const MODEM_STATUS_REGISTER: u64 = 6;
+const SCRATCH_REGISTER: u64 = 7;
+
+const IER_RECEIVED_DATA: u8 = 1;
+const IER_TRANSMIT_EMPTY: u8 = 1 << 1;
+const FCR_ENABLE: u8 = 1;
+const FCR_CLEAR_RECEIVER: u8 = 1 << 1;
+const ISR_NONE: u8 = 1;
+const ISR_TRANSMIT_EMPTY: u8 = 0b010;
+const ISR_RECEIVED_DATA: u8 = 0b100;
+const ISR_FIFO_ENABLED: u8 = 0b1100_0000;
+// The divisor latch replaces the holding and interrupt enable registers.
+const LCR_DIVISOR_LATCH: u8 = 1 << 7;
+// The transmitter is empty as well as its holding register.
+const LSR_TRANSMITTER_EMPTY: u8 = 1 << 6;
+// CTS, DSR and DCD: the host is always there.
+const MSR_CONNECTED: u8 = 0b1011_0000;
+const FIFO_SIZE: usize = 16;
+
+/// Where the bytes sent by the guest go.
+#[derive(Clone, Debug, Default)]
+pub enum Sink {
+    #[default]
+    Stdout,
+    Buffer(Arc<Mutex<Vec<u8>>>),
+    Channel(Sender<u8>),
+    Discard,
+}
+
+impl Sink {
+    fn send(&self, byte: u8) {
+        match self {
+            Sink::Stdout => {
+                let mut stdout = std::io::stdout();
+                // Console output is best effort.
+                let _ = stdout.write_all(&[byte]).and_then(|_| stdout.flush());
+            },
+            Sink::Buffer(buffer) => buffer.lock().unwrap_or_else(|e| e.into_inner()).push(byte),
+            // A closed channel drops the output like a disconnected line.
+            Sink::Channel(sender) => {
+                let _ = sender.send(byte);
+            },
+            Sink::Discard => {},
+        }
+    }
+}
+
+/// Where the bytes received by the guest come from. Input is pulled
+/// without blocking, whatever isn't there yet arrives on a later tick.
+#[derive(Clone, Debug, Default)]
+pub enum Source {
+    #[default]
+    Empty,
+    Buffer(Arc<Mutex<VecDeque<u8>>>),
+    Channel(Arc<Mutex<Receiver<u8>>>),
+}
+
+impl Source {
+    pub fn from_bytes(bytes: &[u8]) -> Source {
+        Source::Buffer(Arc::new(Mutex::new(bytes.iter().copied().collect())))
+    }
+
+    fn receive(&self) -> Option<u8> {
+        match self {
+            Source::Empty => None,
+            Source::Buffer(buffer) => buffer.lock().unwrap_or_else(|e| e.into_inner()).pop_front(),
+            Source::Channel(receiver) => receiver.lock().unwrap_or_else(|e| e.into_inner()).try_recv().ok(),
+        }
+    }
+}
+
+/// A 16550 UART connected to the host. Transmission is immediate, so the
+/// transmitter is always empty, and received bytes wait in a 16 byte FIFO,
+/// or a single holding register while the FIFO is disabled. It raises
+/// UART_IRQ while received data or an empty transmitter is pending and
+/// enabled in the interrupt enable register. Only byte accesses are
+/// supported.
+#[derive(Clone, Debug, Default)]
+pub struct SoftUart {
+    sink: Sink,
+    source: Source,
+    received: VecDeque<u8>,
+    interrupt_enable: u8,
+    fifo_control: u8,
+    line_control: u8,
+    modem_control: u8,
+    scratch: u8,
+    divisor: u16,
+    // The empty transmitter interrupt, cleared by reading the interrupt
+    // status register while it is reported or by writing to the holding
+    // register.
+    transmit_empty: bool,
+}
+
+impl SoftUart {
+    pub fn with_io(sink: Sink, source: Source) -> SoftUart {
+        SoftUart {
+            sink,
+            source,
+            ..SoftUart::default()
+        }
+    }
+
+    fn fifo_size(&self) -> usize {
+        if self.fifo_control & FCR_ENABLE != 0 {
+            FIFO_SIZE
+        } else {
+            1
+        }
+    }
+
+    fn divisor_latch(&self) -> bool {
+        self.line_control & LCR_DIVISOR_LATCH != 0
+    }
+
+    // The pending interrupt of highest priority, received data first.
+    fn interrupt_status(&self) -> u8 {
+        if self.interrupt_enable & IER_RECEIVED_DATA != 0 && !self.received.is_empty() {
+            ISR_RECEIVED_DATA
+        } else if self.interrupt_enable & IER_TRANSMIT_EMPTY != 0 && self.transmit_empty {
+            ISR_TRANSMIT_EMPTY
+        } else {
+            ISR_NONE
+        }
+    }
+}
+
+impl Uart for SoftUart {
+    type Interrupting = bool;
+    type ReceiverTransmitter = u8;
+    type Exception = MemError;
+
+    fn is_interrupting(&self) -> bool {
+        self.interrupt_status() != ISR_NONE
+    }
+
+    fn receive(&mut self) {
+        while self.received.len() < self.fifo_size() {
+            match self.source.receive() {
+                Some(byte) => self.received.push_back(byte),
+                None => break,
+            }
+        }
+    }
+
+    fn read(&mut self, index: u64, size: u8) -> Result<u64, Self::Exception> {
+        if size != BYTE {
+            return Err(MemError::OutOfBounds);
+        }
+        let val = match index {
+            Self::UART_RECEIVING_HOLDING_REGISTER if self.divisor_latch() => self.divisor as u8,
+            Self::UART_RECEIVING_HOLDING_REGISTER => {
+                self.receive();
+                self.received.pop_front().unwrap_or(0)
+            },
+            Self::UART_INTERRUPT_ENABLE_REGISTER if self.divisor_latch() => (self.divisor >> 8) as u8,
+            Self::UART_INTERRUPT_ENABLE_REGISTER => self.interrupt_enable,
+            Self::UART_INTERRUPT_STATUS_REGISTER => {
+                let status = self.interrupt_status();
+                if status == ISR_TRANSMIT_EMPTY {
+                    self.transmit_empty = false;
+                }
+                let fifo = if self.fifo_control & FCR_ENABLE != 0 { ISR_FIFO_ENABLED } else { 0 };
+                status | fifo
+            },
+            LINE_CONTROL_REGISTER => self.line_control,
+            MODEM_CONTROL_REGISTER => self.modem_control,
+            Self::UART_LINE_STATUS_REGISTER => {
+                // Polling drivers don't wait for a tick to see new input.
+                self.receive();
+                let ready = if self.received.is_empty() { 0 } else { Self::UART_LINE_STATUS_REGISTER_RECEIVER as u8 };
+                ready | Self::UART_LINE_STATUS_REGISTER_SENDER as u8 | LSR_TRANSMITTER_EMPTY
+            },
+            MODEM_STATUS_REGISTER => MSR_CONNECTED,
+            SCRATCH_REGISTER => self.scratch,
+            _ => return Err(MemError::OutOfBounds),
+        };
+        Ok(u64::from(val))
+    }
+
+    fn write(&mut self, index: u64, value: u8, size: u8) -> Result<(), Self::Exception> {
+        if size != BYTE {
+            return Err(MemError::OutOfBounds);
+        }
+        match index {
+            Self::UART_TRANSMIT_HOLDING_REGISTER if self.divisor_latch() => {
+                self.divisor = (self.divisor & 0xff00) | u16::from(value);
+            },
+            Self::UART_TRANSMIT_HOLDING_REGISTER => {
+                self.sink.send(value);
+                // Sent right away, so the holding register is empty again.
+                self.transmit_empty = true;
+            },
+            Self::UART_INTERRUPT_ENABLE_REGISTER if self.divisor_latch() => {
+                self.divisor = (self.divisor & 0xff) | (u16::from(value) << 8);
+            },
+            Self::UART_INTERRUPT_ENABLE_REGISTER => {
+                if value & IER_TRANSMIT_EMPTY != 0 && self.interrupt_enable & IER_TRANSMIT_EMPTY == 0 {
+                    self.transmit_empty = true;
+                }
+                self.interrupt_enable = value & (IER_RECEIVED_DATA | IER_TRANSMIT_EMPTY);
+            },
+            Self::UART_FIFO_CONTROL_REGISTER => {
+                if value & FCR_CLEAR_RECEIVER != 0 || (value ^ self.fifo_control) & FCR_ENABLE != 0 {
+                    self.received.clear();
+                }
+                self.fifo_control = value;
+            },
+            LINE_CONTROL_REGISTER => self.line_control = value,
+            MODEM_CONTROL_REGISTER => self.modem_control = value,
+            // The line status is read only.
+            Self::UART_LINE_STATUS_REGISTER | MODEM_STATUS_REGISTER => {},
+            SCRATCH_REGISTER => self.scratch = value,
+            _ => return Err(MemError::OutOfBounds),
+        }
+        Ok(())
+    }
+}
@@ -1,59 +1,65 @@
+use crate::bus::VIRTIO_BASE;
+
+// A legacy virtio MMIO block device. Registers are given by absolute
+// address, an access to [START, END) reaches the register.
 pub trait Vio: Default {
-    const VIRTIO_IRQ: u64;
-    const VRING_DESC_SIZE: u64;
-    const QUEUE_SIZE: u64;
-    const SECTOR_SIZE: u64;
-    const VIRTQ_DESC_F_NEXT: u64;
-    const VIRTQ_DESC_F_WRITE: u64;
-    const _VIRTQ_DESC_F_INDIRECT: u64;
-    const MAGIC_START: u64;
-    const MAGIC_END: u64;
-    const VERSION_START: u64;
-    const VERSION_END: u64;
-    const DEVICE_ID_START: u64;
-    const DEVICE_ID_END: u64;
-    const VENDOR_ID_START: u64;
-    const VENDER_ID_END: u64;
-    const DEVICES_FEATURES_START: u64;
-    const DEVICES_FEATURES_END: u64;
-    const DEVICE_FEATURES_SELECTION_START: u64;
-    const DEVICE_FEATURES_SELF_END: u64;
-    const DRIVER_FEATURES_START: u64;
-    const DRIVER_FEATURES_END: u64;
-    const DRIVER_FEATURES_SELECTION_START: u64;
-    const DRIVER_FEATURES_SELECTION_END: u64;
-    const GUEST_PAGE_SIZE_START: u64;
-    const GUEST_PAGE_SIZE_END: u64;
-    const QUEUE_SELECTION_START: u64;
-    const QUEUE_SELECTION_END: u64;
-    const QUEUE_NUM_MAX_START: u64;
-    const QUEUE_NUM_MAX_END: u64;
-    const QUEUE_ALIGN_START: u64;
-    const QUEUE_ALIGN_END: u64;
-    const QUEUE_PFN_START: u64;
-    const QUEUE_PFN_END: u64;
-    const QUEUE_NOTIFY_START: u64;
-    const QUEUE_NOTIFY_END: u64;
-    const INTERRUPT_STATUS_START: u64;
-    const INTERRUPT_STATUS_END: u64;
-    const INTERRUPT_ACKNOWLEDGEMENT_START: u64;
-    const INTERRUPT_ACKNOWLEDGEMENT_END: u64;
-    const STATUS_START: u64;
-    const STATUS_END: u64;
-    const CONFIG_START: u64;
-    const CONFIG_END: u64;
-    pub type VirtualQueueAddress;
-    pub type VirtualQueueDescriptor;
-    pub type VirtualQueueAvailability;
-    pub type Exception;
-    pub type Cpu;
+    const VIRTIO_IRQ: u64 = 1;
+    const VRING_DESC_SIZE: u64 = 16;
+    const QUEUE_SIZE: u64 = 8;
+    const SECTOR_SIZE: u64 = 512;
+    const VIRTQ_DESC_F_NEXT: u64 = 1;
+    const VIRTQ_DESC_F_WRITE: u64 = 2;
+    const _VIRTQ_DESC_F_INDIRECT: u64 = 4;
+    const MAGIC_START: u64 = VIRTIO_BASE;
+    const MAGIC_END: u64 = VIRTIO_BASE + 0x4;
+    const VERSION_START: u64 = VIRTIO_BASE + 0x4;
+    const VERSION_END: u64 = VIRTIO_BASE + 0x8;
+    const DEVICE_ID_START: u64 = VIRTIO_BASE + 0x8;
+    const DEVICE_ID_END: u64 = VIRTIO_BASE + 0xc;
+    const VENDOR_ID_START: u64 = VIRTIO_BASE + 0xc;
+    const VENDER_ID_END: u64 = VIRTIO_BASE + 0x10;
+    const DEVICES_FEATURES_START: u64 = VIRTIO_BASE + 0x10;
+    const DEVICES_FEATURES_END: u64 = VIRTIO_BASE + 0x14;
+    const DEVICE_FEATURES_SELECTION_START: u64 = VIRTIO_BASE + 0x14;
+    const DEVICE_FEATURES_SELF_END: u64 = VIRTIO_BASE + 0x18;
+    const DRIVER_FEATURES_START: u64 = VIRTIO_BASE + 0x20;
+    const DRIVER_FEATURES_END: u64 = VIRTIO_BASE + 0x24;
+    const DRIVER_FEATURES_SELECTION_START: u64 = VIRTIO_BASE + 0x24;
+    const DRIVER_FEATURES_SELECTION_END: u64 = VIRTIO_BASE + 0x28;
+    const GUEST_PAGE_SIZE_START: u64 = VIRTIO_BASE + 0x28;
+    const GUEST_PAGE_SIZE_END: u64 = VIRTIO_BASE + 0x2c;
+    const QUEUE_SELECTION_START: u64 = VIRTIO_BASE + 0x30;
+    const QUEUE_SELECTION_END: u64 = VIRTIO_BASE + 0x34;
+    const QUEUE_NUM_MAX_START: u64 = VIRTIO_BASE + 0x34;
+    const QUEUE_NUM_MAX_END: u64 = VIRTIO_BASE + 0x38;
+    const QUEUE_ALIGN_START: u64 = VIRTIO_BASE + 0x3c;
+    const QUEUE_ALIGN_END: u64 = VIRTIO_BASE + 0x40;
+    const QUEUE_PFN_START: u64 = VIRTIO_BASE + 0x40;
+    const QUEUE_PFN_END: u64 = VIRTIO_BASE + 0x44;
+    const QUEUE_NOTIFY_START: u64 = VIRTIO_BASE + 0x50;
+    const QUEUE_NOTIFY_END: u64 = VIRTIO_BASE + 0x54;
+    const INTERRUPT_STATUS_START: u64 = VIRTIO_BASE + 0x60;
+    const INTERRUPT_STATUS_END: u64 = VIRTIO_BASE + 0x64;
+    const INTERRUPT_ACKNOWLEDGEMENT_START: u64 = VIRTIO_BASE + 0x64;
+    const INTERRUPT_ACKNOWLEDGEMENT_END: u64 = VIRTIO_BASE + 0x68;
+    const STATUS_START: u64 = VIRTIO_BASE + 0x70;
+    const STATUS_END: u64 = VIRTIO_BASE + 0x74;
+    const CONFIG_START: u64 = VIRTIO_BASE + 0x100;
+    const CONFIG_END: u64 = VIRTIO_BASE + 0x108;
+    type VirtualQueueAddress;
+    type VirtualQueueDescriptor;
+    type VirtualQueueAvailability;
+    type Exception;
+    type Cpu;
 
     fn new_virtual_queue_address(self) -> Self::VirtualQueueAddress;
     fn new_virtual_queue_descriptor(self) -> Self::VirtualQueueDescriptor;
     fn new_virtual_queue_availability(self, cpu: Self::Cpu) -> Result<Self::VirtualQueueAvailability, Self::Exception>;
+    // Whether the device raises VIRTIO_IRQ.
+    fn is_interrupting(&self) -> bool;
+    fn read(&self, addr: u64, size: u8) -> Result<u64, Self::Exception>;
+    fn write(&mut self, addr: u64, value: u64, size: u8) -> Result<(), Self::Exception>;
     fn new() -> Self {
         Self::default()
-    }; 
-
-
+    }
 }
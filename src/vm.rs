@@ -1,20 +1,45 @@
 #![allow(unused, unused_mut, dead_code)]
 use crate::soft::SoftThread;
+use crate::encoding::EncodingTable;
 use crate::extensions::{Extension};
 use crate::exceptions::Exception;
-use crate::memory::{Memory, Dram};
+use crate::memory::{Memory, Dram, MemoryLayout};
 use crate::register::RegisterValue;
-use crate::state::StateObject;
+use crate::syscall::ExitSyscall;
+use crate::state::{Journal, StateObject, StorageDiff, StorageObject};
+use crate::machine::Support;
+use crate::consts::INDEX_SIZE;
+use crate::elf::{ElfError, ELF_MAGIC};
 use std::fmt::{Display, Formatter};
 use std::error::Error;
 use std::hash::Hash;
-use std::fs::{File, metadata};
+use std::cell::RefCell;
+use std::fs::File;
 use std::io::Read;
 
 
 pub const STACKSIZE: u64 = 4096u64;
 pub const INST_LEN: u64 = 4u64;
 pub type CpuResult = Result<(), Exception>;
+pub type LoadResult = Result<(), LoadError>;
+
+#[derive(Debug, PartialEq)]
+pub enum LoadError {
+    // There is no code at the address, or it could not be read.
+    MissingCode,
+    // The code, with its size, does not fit in memory.
+    OversizedCode(usize),
+    // The code is empty, not made of whole instructions or a bad ELF.
+    MalformedCode,
+}
+
+impl Display for LoadError {
+    fn fmt(&self, f: &mut Formatter) -> std::fmt::Result {
+        write!(f, "{:?}", self)
+    }
+}
+
+impl Error for LoadError {}
 
 #[derive(Debug)]
 pub struct ProgramBuffer {
@@ -37,44 +62,116 @@ impl Cpu {
         Cpu::default()    
     }
 
+    pub fn with_layout(layout: MemoryLayout) -> Cpu {
+        let enc_table = EncodingTable::default();
+        let mut core = SoftThread::new(enc_table, layout);
+        core.add_syscall(Box::new(ExitSyscall));
+        Cpu {
+            core,
+            ext: Extension::G,
+            pb: ProgramBuffer::default()
+        }
+    }
+
+    /// Runs the loaded program until it ends through the `EXIT` syscall, or
+    /// another syscall handler halts the hart. An exception the guest does
+    /// not handle, running out of gas included, stops the run and is
+    /// returned.
     pub fn run(&mut self) -> CpuResult {
-        while self.core.pc < (self.core.program.len() as u64) {
-            self.core.execute();
+        self.core.set_running(true);
+        while self.core.running() {
+            if let Err(e) = self.core.execute() {
+                self.core.set_running(false);
+                return Err(e);
+            }
         }
+        self.core.set_running(false);
         Ok(())
     }
+
+    /// Runs the loaded program against the storage journal the guest writes
+    /// through. The writes are committed when the run succeeds and thrown
+    /// away when the run or the commit fails.
+    pub fn run_with_journal<S: StorageObject + Clone>(&mut self, journal: &RefCell<Journal<S>>) -> Result<StorageDiff, Exception> {
+        if let Err(e) = self.run() {
+            journal.borrow_mut().rollback();
+            return Err(e);
+        }
+
+        let mut journal = journal.borrow_mut();
+        journal.commit().map_err(|e| {
+            journal.rollback();
+            Exception::State(e.to_string())
+        })
+    }
     
-    pub fn load_from_file(&mut self, path: String) -> CpuResult {
-        let mut f = File::open(&path).expect("file not found");
-        let meta = metadata(&path).expect("unable to read metadata");
-        if meta.len() > (STACKSIZE * INST_LEN) {
-            let mut buffer = vec![];
-            f.read_to_end(&mut buffer);
-            self.pb.buf = buffer;
-            self.pb.cursor = 0;
-            return Err(Exception::LoadFromBuffer);
-        } else {
-            let mut buffer = vec![0; meta.len() as usize];
-            f.read(&mut buffer).expect("buffer overflow");
-            self.core.load_program(buffer)?;
+    pub fn load_from_file(&mut self, path: String) -> LoadResult {
+        let mut buffer = vec![];
+        File::open(&path)
+            .and_then(|mut f| f.read_to_end(&mut buffer))
+            .map_err(|_| LoadError::MissingCode)?;
+
+        self.load_code(buffer)
+    }
+
+    pub fn load_from_state<S: StateObject>(&mut self, state: S, addr: S::Address) -> LoadResult {
+        let program: Vec<u8> = state.get_code(&addr)
+            .map_err(|_| LoadError::MissingCode)?
+            .into();
+
+        self.load_code(program)
+    }
+
+    /// Loads an ELF image or raw instructions into executable memory. Raw
+    /// code is placed at the pc one page at a time through the program
+    /// buffer.
+    pub fn load_code(&mut self, program: Vec<u8>) -> LoadResult {
+        if program.is_empty() {
+            return Err(LoadError::MalformedCode);
+        }
+        let size = program.len();
+        // The loader checks the segments of an ELF image fit, not the file.
+        if program.starts_with(&ELF_MAGIC) {
+            return match self.core.load_elf(&program, true) {
+                Ok(_) => Ok(()),
+                Err(Exception::InvalidElf(ElfError::Oversized(size))) => Err(LoadError::OversizedCode(size as usize)),
+                Err(Exception::InvalidElf(ElfError::SegmentOutOfBounds)) => Err(LoadError::OversizedCode(size)),
+                Err(_) => Err(LoadError::MalformedCode),
+            };
+        }
+        // Code can use the memory below the heap.
+        if size as u64 > self.core.layout().code_size() {
+            return Err(LoadError::OversizedCode(size));
+        }
+        if !size.is_multiple_of(self.core.inst_align() as usize) {
+            return Err(LoadError::MalformedCode);
+        }
+
+        self.pb.buf = program;
+        self.pb.cursor = 0;
+        while let Some((offset, chunk)) = self.pb.next_chunk(INDEX_SIZE) {
+            self.core.load_code_chunk(offset as u64, chunk)
+                .map_err(|_| LoadError::OversizedCode(size))?;
         }
+        self.core.program = std::mem::take(&mut self.pb.buf);
+        self.pb.cursor = 0;
+
         Ok(())
     }
+}
 
-    pub fn load_from_state<S: StateObject>(&mut self, state: S, addr: S::Address) -> CpuResult {
-        if let Ok(program) = state.get_code(&addr) {
-            let program: Vec<u8> = program.into();
-            if program.len() > ((STACKSIZE * INST_LEN) as usize) {
-                self.core.load_program(program[..(STACKSIZE * INST_LEN) as usize].into());
-                self.pb.buf = program[((STACKSIZE * INST_LEN) as usize)..].to_vec();
-                self.pb.cursor = (STACKSIZE * INST_LEN) as usize;
-                return Err(Exception::LoadFromBuffer);
-            }
-
-            self.core.load_program(program.into())?;
+impl ProgramBuffer {
+    // Returns the next chunk of at most `size` bytes with its offset in
+    // the program, and moves the cursor past it.
+    pub fn next_chunk(&mut self, size: usize) -> Option<(usize, Vec<u8>)> {
+        if self.cursor >= self.buf.len() {
+            return None;
         }
+        let start = self.cursor;
+        let end = std::cmp::min(start + size, self.buf.len());
+        self.cursor = end;
 
-        return Err(Exception::InvalidAddr)
+        Some((start, self.buf[start..end].to_vec()))
     }
 }
 
@@ -90,12 +187,7 @@ impl Default for ProgramBuffer {
 
 impl Default for Cpu {
     fn default() -> Cpu {
-        let mut softs = SoftThread::<u64, f64, Dram>::default(); 
-        Cpu {
-            core: softs,
-            ext: Extension::G,
-            pb: ProgramBuffer::default()
-        }
+        Cpu::with_layout(MemoryLayout::default())
     }
 }
 